@@ -94,26 +94,24 @@ impl Robot {
         Robot { facing: default_facing, color_to_paint: Color::Black, pos: start_pos, runner }
     }
 
-    fn emit(&mut self) -> (isize, isize) {
+    //returns None if the program stopped before emitting both outputs
+    fn emit(&mut self) -> Option<(isize, isize)> {
 
-        let mut outputs = Vec::with_capacity(2);
+        let outputs = self.runner.outputs_chunked(2).next()?;
 
-        while outputs.len() < 2 {
-            let next_opcode = self.runner.parse_cur_opcode();
-
-            let got_output = self.runner.exec_opcode(next_opcode);
-
-            if got_output {
-                outputs.push(self.runner.output().unwrap());
-            }
+        match outputs.expect("Intcode program faulted")[..] {
+            [paint_bit, turn_bit] => Some((paint_bit, turn_bit)),
+            _ => None,
         }
 
-        (outputs[0], outputs[1])
-
     }
 
-    fn update_instructions(&mut self) {
-        let (paint_bit, turn_bit) = self.emit();
+    //returns false if the program stopped instead of giving instructions
+    fn update_instructions(&mut self) -> bool {
+        let (paint_bit, turn_bit) = match self.emit() {
+            Some(instructions) => instructions,
+            None => return false,
+        };
 
         match paint_bit {
             0 => self.color_to_paint = Color::Black,
@@ -126,6 +124,8 @@ impl Robot {
             1 => self.facing.turn_right(),
             _ => panic!("Got illegal turn bit"),
         };
+
+        true
     }
 
     pub fn has_halted(&self) -> bool {
@@ -147,7 +147,9 @@ impl Robot {
             Color::White => 1,
         };
         self.runner.push_input(input_to_use);
-        self.update_instructions();
+        if !self.update_instructions() {
+            return pos_painted;
+        }
 
         grid[self.pos.row][self.pos.col] = self.color_to_paint;
 
@@ -1,7 +1,6 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::{HashMap, VecDeque};
 use crate::intcode::*;
-use std::io;
 
 #[aoc_generator(day13)]
 pub fn input_generator(input: &str) -> Vec<isize> {
@@ -74,10 +73,15 @@ fn create_screen() -> Vec<Vec<Tile>>{
 //it sends Opcode 0. So rather than crash when receiving Opcode 0, I made my
 //runner restart when Opcode 0 is received, however the memory remains,
 //in other words, touching the floor no longer loses the game.
-//so it plays itself.
+//so it plays itself. opcode 0 is an error by default, so Reboot is registered as an extension.
+//the opcode 0 comes right after the Halt the game runs when the ball is lost, and a halted runner
+//stays halted, so it's resumed past the Halt while there are blocks left.
 fn play(runner: IntcodeRunner) -> isize {
 
     let mut runner = runner;
+    let mut instructions = InstructionSet::standard();
+    instructions.register(extensions::reboot());
+    runner.set_instructions(instructions);
     let mut screen = create_screen();
     let mut score = 0;
 
@@ -85,18 +89,25 @@ fn play(runner: IntcodeRunner) -> isize {
     loop {
         while buf.len() < 3 {
 
-            let next_opcode = runner.parse_cur_opcode();
+            match runner.run().expect("Intcode program faulted") {
+                RunState::NeedsInput => {
+//                    let joystick_position = OpcodeRunner::ask_for_input();
+                    let joystick_position = 0; //hacked
+                    runner.push_input(joystick_position);
+                }
 
-            if let Opcode::In(_) = next_opcode {
-//                let joystick_position = OpcodeRunner::ask_for_input();
-                let joystick_position = 0; //hacked
-                runner.push_input(joystick_position);
-            }
+                RunState::Output(output) => buf.push_back(output),
 
-            let got_output = runner.exec_opcode(next_opcode);
+                RunState::Halted => {
+                    let blocks_left = screen.iter().flatten().any(|&tile| tile == Tile::Block);
+                    if !blocks_left {
+                        break;
+                    }
 
-            if got_output {
-                buf.push_back(runner.output().unwrap());
+                    runner.resume_after_halt();
+                }
+
+                RunState::Watchpoint(_) | RunState::LimitReached(_) => (),
             }
 
 
@@ -142,33 +153,15 @@ fn play(runner: IntcodeRunner) -> isize {
 pub fn part1(mem: &[isize]) -> usize {
 
     let mut tiles: HashMap<Pos, Tile> = HashMap::new();
-    let mut buf = VecDeque::with_capacity(3);
 
     let mut runner = IntcodeRunner::new(mem);
 
-    loop {
-        while buf.len() < 3 {
-
-            let next_opcode = runner.parse_cur_opcode();
-
-            let got_output = runner.exec_opcode(next_opcode);
-
-            if got_output {
-                buf.push_back(runner.output().unwrap());
-            }
-        }
-
-        if !runner.has_halted() {
-            let (pos_x, pos_y) = (buf.pop_front().unwrap(), buf.pop_front().unwrap());
+    for triple in runner.outputs_chunked(3) {
+        if let [pos_x, pos_y, tile] = triple.expect("Intcode program faulted")[..] {
             let t_pos = Pos { x: pos_x as usize, y: pos_y as usize };
-            let t_type: Tile = buf.pop_front().unwrap().into();
+            let t_type: Tile = tile.into();
             tiles.insert(t_pos, t_type);
         }
-
-        else {
-            break;
-        }
-
     }
 
 
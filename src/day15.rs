@@ -12,6 +12,9 @@ pub fn input_generator(input: &str) -> Vec<isize> {
         .collect()
 }
 
+/// more than enough for the droid to make a move and report it.
+const STEPS_PER_MOVE: u64 = 100_000;
+
 #[derive(Clone, Copy, Eq, PartialEq)]
 enum Tile {
     Wall,
@@ -75,16 +78,11 @@ fn adjacent_positions_from(pos: Pos) -> Vec<(Pos, isize)>{
 
 fn advance_runner_until_output(runner: &mut IntcodeRunner, input: isize) -> MoveResult {
     runner.push_input(input);
+    runner.set_step_budget(Some(STEPS_PER_MOVE));
 
-    loop {
-        let next_opcode = runner.parse_cur_opcode();
-
-        let got_output = runner.exec_opcode(next_opcode);
-
-        if got_output {
-            let output = runner.output().unwrap();
-            return MoveResult::from(output);
-        }
+    match runner.run().expect("Intcode program faulted") {
+        RunState::Output(output) => MoveResult::from(output),
+        run_state => panic!("the droid didn't report its move, got {:?}", run_state),
     }
 }
 
@@ -1,6 +1,6 @@
 use aoc_runner_derive::{aoc, aoc_generator};
-use crate::intcode::{IntcodeRunner, Opcode};
-use itertools::{all, Itertools};
+use crate::intcode::{AsciiIntcode, AsciiOutput, IntcodeRunner};
+use itertools::all;
 use std::io;
 
 #[aoc_generator(day17)]
@@ -13,39 +13,14 @@ pub fn input_generator(input: &str) -> Vec<isize> {
 }
 
 fn get_camera_image(mem: &[isize]) -> Vec<Vec<char>> {
-    let mut image: Vec<Vec<char>> = Vec::new();
-    let mut runner = IntcodeRunner::new(mem);
+    let mut camera = AsciiIntcode::new(IntcodeRunner::new(mem));
 
-    let mut buf: Vec<char> = Vec::new();
-    while !runner.has_halted() {
-
-        let next_pixel =
-        loop {
-            let next_opcode = runner.parse_cur_opcode();
-
-            let got_output = runner.exec_opcode(next_opcode);
-
-            if got_output {
-                let output_u8 = runner.output().unwrap() as u8;
-                break output_u8
-            }
-        };
-
-        match next_pixel {
-            10 => {
-                image.push(buf);
-
-                //just in case it wasn't obvious, this just change what "buf" is pointing at
-                //in other words, the previous buf lives on, now owned by "image"
-                buf = Vec::new();
-            }
-
-            _ => {
-                buf.push(next_pixel as char);
-            }
-        };
-
-    }
+    let mut image: Vec<Vec<char>> = camera
+        .read_lines()
+        .expect("camera output wasn't text")
+        .iter()
+        .map(|line| line.chars().collect())
+        .collect();
 
     //the way the input is sent, an empty row would be added as the last element
     image.pop();
@@ -253,7 +228,7 @@ fn part2(mem: &[isize]) -> Option<isize> {
     //part 2 requires changing memory address 0 from 1 to 2.
     let mut mem = mem.to_vec();
     mem[0] = 2;
-    let mut runner = IntcodeRunner::new(&mem);
+    let runner = IntcodeRunner::new(&mem);
 
     //the following solutions were worked out by hand.
     let main_movement_routine = "A,B,A,B,C,C,B,A,B,C";
@@ -262,27 +237,18 @@ fn part2(mem: &[isize]) -> Option<isize> {
     let c = "L,10,R,12,R,8";
     let video_feed_preference = "n";
 
-    let newline: u8 = 10;
-
+    let mut robot = AsciiIntcode::new(runner);
     for &input_str in [main_movement_routine, a, b, c, video_feed_preference].iter() {
-        for ch in input_str.chars() {
-            runner.push_input_front(ch as isize);
-        }
-        runner.push_input_front(newline as isize);
+        robot.send_line(input_str);
     }
 
-    let mut last_output = None;
-    while !runner.has_halted() {
-        let next_opcode = runner.parse_cur_opcode();
-
-        let got_output = runner.exec_opcode(next_opcode);
-
-        if got_output {
-            let output = runner.output().unwrap();
-            last_output = Some(output);
+    //the dust count is the one output that isn't ASCII
+    let mut dust = None;
+    while let Some(output) = robot.next_output().expect("Intcode program faulted") {
+        if let AsciiOutput::Value(value) = output {
+            dust = Some(value);
         }
-
     }
 
-    last_output
-}
\ No newline at end of file
+    dust
+}
@@ -2,40 +2,39 @@ use crate::intcode::*;
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day2)]
-pub fn input_generator(input: &str) -> Vec<isize> {
-    input
+pub fn input_generator(input: &str) -> Program {
+    let mem: Vec<isize> = input
         .split(',')
         .map(|val| val.parse())
         .map(|res| res.unwrap())
-        .collect()
+        .collect();
+
+    Program::new(&mem)
 }
 
-pub fn run_with_noun_and_verb(mem: &[isize], noun: isize, verb: isize) -> isize {
-    let mut runner = IntcodeRunner::new(mem);
+pub fn run_with_noun_and_verb(program: &Program, noun: isize, verb: isize) -> isize {
+    let mut runner = program.runner();
 
     runner.set_noun(noun);
     runner.set_verb(verb);
 
-    while !runner.has_halted() {
-        let cur_opcode = runner.parse_cur_opcode();
-        runner.exec_opcode(cur_opcode);
-    }
+    while runner.run().expect("Intcode program faulted") != RunState::Halted {}
 
     runner.value_at_pos_0()
 }
 
 #[aoc(day2, part1)]
-pub fn part1(mem: &[isize]) -> isize {
-    run_with_noun_and_verb(mem, 12, 2)
+pub fn part1(program: &Program) -> isize {
+    run_with_noun_and_verb(program, 12, 2)
 }
 
 #[aoc(day2, part2)]
-pub fn part2(mem: &[isize]) -> isize {
+pub fn part2(program: &Program) -> isize {
     const PUZZLE_OUTPUT: isize = 19_690_720;
 
     for verb in 0..=99 {
         for noun in 0..=99 {
-            if run_with_noun_and_verb(mem, noun, verb) == PUZZLE_OUTPUT {
+            if run_with_noun_and_verb(program, noun, verb) == PUZZLE_OUTPUT {
                 return (noun * 100) + verb;
             }
         }
@@ -1,3 +1,4 @@
+use crate::intcode::devices::Repeat;
 use crate::intcode::*;
 use aoc_runner_derive::{aoc, aoc_generator};
 
@@ -15,13 +16,9 @@ fn part1(mem: &[isize]) -> Option<isize> {
     const INPUT: isize = 1;
 
     let mut runner = IntcodeRunner::new(mem);
-    runner.set_input_consume_mode(InputMode::SingleInput);
-    runner.push_input(INPUT);
-
-    while !runner.has_halted() {
-        let cur_opcode = runner.parse_cur_opcode();
-        runner.exec_opcode(cur_opcode);
-    }
+    runner
+        .run_with(&mut Repeat(INPUT), &mut |_| ())
+        .expect("Intcode program faulted");
 
     runner.output()
 }
@@ -31,13 +28,9 @@ fn part2(mem: &[isize]) -> Option<isize> {
     const INPUT: isize = 5;
 
     let mut runner = IntcodeRunner::new(mem);
-    runner.set_input_consume_mode(InputMode::SingleInput);
-    runner.push_input(INPUT);
-
-    while !runner.has_halted() {
-        let cur_opcode = runner.parse_cur_opcode();
-        runner.exec_opcode(cur_opcode);
-    }
+    runner
+        .run_with(&mut Repeat(INPUT), &mut |_| ())
+        .expect("Intcode program faulted");
 
     runner.output()
 }
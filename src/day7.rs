@@ -1,9 +1,11 @@
+use crate::intcode::threads::Cluster;
 use crate::intcode::*;
 use aoc_runner_derive::{aoc, aoc_generator};
 extern crate permute;
 use itertools::all;
 use permute::permute;
 use std::borrow::BorrowMut;
+use std::collections::VecDeque;
 
 pub struct Amp {
     _phase: isize,
@@ -24,7 +26,8 @@ impl Amp {
 pub struct AmpChain {
     /// a chain of Amps, each feeding the next one's input in a chain.
     amps: Vec<Amp>,
-    //    last_output: Option<isize>,
+    /// the inputs waiting for the running amp, i.e. the outputs of the amp before it.
+    signals: VecDeque<isize>,
     running_amp: usize,
 }
 
@@ -52,10 +55,9 @@ impl AmpChain {
             amps.push(amp);
         }
 
-        amps[0].runner.push_input_front(initial_input); //pushed in front as a workaround
-
         AmpChain {
             amps,
+            signals: vec![initial_input].into_iter().collect(),
             running_amp: 0,
         }
     }
@@ -73,25 +75,22 @@ impl AmpChain {
     }
 
     pub fn run(&mut self, run_mode: RunMode) -> Option<isize> {
-        let mut last_output = None;
+        if run_mode == RunMode::FeedbackLoop {
+            return self.run_feedback_loop();
+        }
 
         while !self.has_halted() {
+            let mut signals = std::mem::take(&mut self.signals);
+            let mut outputs = VecDeque::new();
             let runner = self.current_runner();
 
-            if let Some(output) = last_output.take() {
-                runner.push_input_front(output);
+            if !runner.has_halted() {
+                runner
+                    .run_with(&mut signals, &mut outputs)
+                    .expect("Intcode program faulted");
             }
 
-
-            while !runner.has_halted() {
-                let cur_opcode = runner.parse_cur_opcode();
-                let got_output = runner.exec_opcode(cur_opcode);
-
-                if got_output {
-                    last_output = runner.output();
-                    break;
-                }
-            }
+            self.signals = outputs;
 
             if run_mode == RunMode::SinglePass && self.running_amp == self.num_amps() - 1 {
                 break;
@@ -101,6 +100,39 @@ impl AmpChain {
 
         self.amps[self.num_amps() - 1].runner.output()
     }
+
+    /// runs every amp on its own thread, each feeding the next one and the last one feeding the first.
+    fn run_feedback_loop(&mut self) -> Option<isize> {
+        let mut cluster = Cluster::new();
+        let (mut links, inboxes): (Vec<_>, Vec<_>) = self.amps.iter().map(|_| cluster.channel()).unzip();
+
+        for signal in self.signals.drain(..) {
+            links[self.running_amp].send(signal);
+        }
+
+        let phases: Vec<isize> = self.amps.iter().map(|amp| amp._phase).collect();
+        links.rotate_left(1);
+
+        for ((amp, inbox), link) in self.amps.drain(..).zip(inboxes).zip(links) {
+            cluster.spawn(amp.runner, inbox, link);
+        }
+
+        let finished = match cluster.join() {
+            Ok(finished) => finished,
+            Err(_) => panic!("the amps deadlocked"),
+        };
+
+        self.amps = phases
+            .into_iter()
+            .zip(finished)
+            .map(|(phase, amp)| Amp {
+                _phase: phase,
+                runner: amp.runner,
+            })
+            .collect();
+
+        self.amps[self.num_amps() - 1].runner.output()
+    }
 }
 
 #[aoc_generator(day7)]
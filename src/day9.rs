@@ -16,17 +16,12 @@ fn part1(mem: &[isize]) -> isize {
     let test_mode = 1;
     let mut runner = IntcodeRunner::new(mem);
 
-    let mut outputs: Vec<isize> = Vec::new();
-
     runner.push_input(test_mode);
 
-    while !runner.has_halted() {
-        let cur_opcode = runner.parse_cur_opcode();
-        let got_output = runner.exec_opcode(cur_opcode);
-        if got_output {
-            outputs.push(runner.output().unwrap());
-        }
-    }
+    let mut outputs: Vec<isize> = runner
+        .outputs()
+        .collect::<Result<_, _>>()
+        .expect("Intcode program faulted");
 
 
 //    dbg!(outputs);
@@ -39,17 +34,12 @@ fn part2(mem: &[isize]) -> isize {
     let boost_mode = 2;
     let mut runner = IntcodeRunner::new(mem);
 
-    let mut outputs: Vec<isize> = Vec::new();
-
     runner.push_input(boost_mode);
 
-    while !runner.has_halted() {
-        let cur_opcode = runner.parse_cur_opcode();
-        let got_output = runner.exec_opcode(cur_opcode);
-        if got_output {
-            outputs.push(runner.output().unwrap());
-        }
-    }
+    let mut outputs: Vec<isize> = runner
+        .outputs()
+        .collect::<Result<_, _>>()
+        .expect("Intcode program faulted");
 
 
 //    dbg!(outputs);
@@ -1,22 +1,68 @@
 use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
 use std::io;
-
-#[derive(Eq, PartialEq, Clone)]
-pub enum Opcode {
-    Add(Value, Value, Value),  //1
-    Mul(Value, Value, Value),  //2
-    In(Value),                 //3
-    Out(Value),                //4
-    JumpIfTrue(Value, Value),  //5
-    JumpIfFalse(Value, Value), //6
-    LT(Value, Value, Value),   //7
-    EQ(Value, Value, Value),   //8
-    BaseOffset(Value),         //9
-    Halt,                      //99
-    Reboot,                    //0
+use std::sync::Arc;
+
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+pub mod compiler;
+pub mod debugger;
+pub mod devices;
+pub mod disassembler;
+pub mod extensions;
+pub mod instruction;
+pub mod journal;
+pub mod limits;
+pub mod memory;
+pub mod network;
+pub mod program;
+pub mod session;
+pub mod snapshot;
+pub mod threads;
+pub mod trace;
+pub mod watch;
+pub mod word;
+
+#[cfg(test)]
+mod conformance;
+#[cfg(test)]
+mod fixtures;
+
+use self::compiler::CompiledOp;
+use self::journal::Journal;
+use self::limits::Limits;
+use self::memory::Memory;
+use self::session::{Event, Session};
+pub use self::ascii::{AsciiError, AsciiIntcode, AsciiOutput};
+pub use self::compiler::CompiledProgram;
+pub use self::devices::{InputSource, OutputSink};
+pub use self::extensions::{ExtOp, Extension};
+pub use self::instruction::{Flow, Instruction, InstructionSet};
+pub use self::limits::Limit;
+pub use self::program::Program;
+pub use self::trace::{Trace, TraceRecord};
+pub use self::watch::{Access, WatchAction, WatchHit, Watchpoint};
+pub use self::word::{CheckedI64, Word};
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Opcode<W = isize> {
+    Add(Value<W>, Value<W>, Value<W>),  //1
+    Mul(Value<W>, Value<W>, Value<W>),  //2
+    In(Value<W>),                       //3
+    Out(Value<W>),                      //4
+    JumpIfTrue(Value<W>, Value<W>),     //5
+    JumpIfFalse(Value<W>, Value<W>),    //6
+    LT(Value<W>, Value<W>, Value<W>),   //7
+    EQ(Value<W>, Value<W>, Value<W>),   //8
+    BaseOffset(Value<W>),               //9
+    Halt,                               //99
+    /// an instruction outside the standard set, see instruction.rs and extensions.rs.
+    Extension(ExtOp<W>),
 }
 
-impl Opcode {
+impl<W: Word> Opcode<W> {
     fn num_vals(&self) -> isize {
         match self {
             Opcode::Add(_, _, _)
@@ -26,67 +72,324 @@ impl Opcode {
             Opcode::In(_) | Opcode::Out(_) | Opcode::BaseOffset(_) => 1,
             Opcode::JumpIfTrue(_, _) | Opcode::JumpIfFalse(_, _) => 2,
             Opcode::Halt => 0,
-            Opcode::Reboot => 0,
+            Opcode::Extension(op) => op.params.len() as isize,
+        }
+    }
+
+    /// the two-digit opcode, without parameter modes.
+    pub fn code(&self) -> isize {
+        match self {
+            Opcode::Add(_, _, _) => 1,
+            Opcode::Mul(_, _, _) => 2,
+            Opcode::In(_) => 3,
+            Opcode::Out(_) => 4,
+            Opcode::JumpIfTrue(_, _) => 5,
+            Opcode::JumpIfFalse(_, _) => 6,
+            Opcode::LT(_, _, _) => 7,
+            Opcode::EQ(_, _, _) => 8,
+            Opcode::BaseOffset(_) => 9,
+            Opcode::Halt => 99,
+            Opcode::Extension(op) => op.code,
+        }
+    }
+
+    /// the name used for this opcode by the assembler.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Add(_, _, _) => "add",
+            Opcode::Mul(_, _, _) => "mul",
+            Opcode::In(_) => "in",
+            Opcode::Out(_) => "out",
+            Opcode::JumpIfTrue(_, _) => "jt",
+            Opcode::JumpIfFalse(_, _) => "jf",
+            Opcode::LT(_, _, _) => "lt",
+            Opcode::EQ(_, _, _) => "eq",
+            Opcode::BaseOffset(_) => "arb",
+            Opcode::Halt => "halt",
+            Opcode::Extension(op) => op.mnemonic,
         }
     }
+
+    pub fn params(&self) -> Vec<&Value<W>> {
+        match self {
+            Opcode::Add(val1, val2, val3)
+            | Opcode::Mul(val1, val2, val3)
+            | Opcode::LT(val1, val2, val3)
+            | Opcode::EQ(val1, val2, val3) => vec![val1, val2, val3],
+            Opcode::In(val) | Opcode::Out(val) | Opcode::BaseOffset(val) => vec![val],
+            Opcode::JumpIfTrue(val1, val2) | Opcode::JumpIfFalse(val1, val2) => vec![val1, val2],
+            Opcode::Halt => vec![],
+            Opcode::Extension(op) => op.params.iter().collect(),
+        }
+    }
+
+    /// the parameter at `idx`, like params() but without collecting them.
+    pub fn param(&self, idx: usize) -> Option<&Value<W>> {
+        match (self, idx) {
+            (Opcode::Add(val, _, _), 0)
+            | (Opcode::Mul(val, _, _), 0)
+            | (Opcode::LT(val, _, _), 0)
+            | (Opcode::EQ(val, _, _), 0)
+            | (Opcode::In(val), 0)
+            | (Opcode::Out(val), 0)
+            | (Opcode::BaseOffset(val), 0)
+            | (Opcode::JumpIfTrue(val, _), 0)
+            | (Opcode::JumpIfFalse(val, _), 0)
+            | (Opcode::Add(_, val, _), 1)
+            | (Opcode::Mul(_, val, _), 1)
+            | (Opcode::LT(_, val, _), 1)
+            | (Opcode::EQ(_, val, _), 1)
+            | (Opcode::JumpIfTrue(_, val), 1)
+            | (Opcode::JumpIfFalse(_, val), 1)
+            | (Opcode::Add(_, _, val), 2)
+            | (Opcode::Mul(_, _, val), 2)
+            | (Opcode::LT(_, _, val), 2)
+            | (Opcode::EQ(_, _, val), 2) => Some(val),
+            (Opcode::Extension(op), idx) => op.params.get(idx),
+            _ => None,
+        }
+    }
+
+    /// the memory words this opcode is stored as, parameter modes included.
+    pub fn encode(&self) -> Vec<W> {
+        let params = self.params();
+
+        let modes = params
+            .iter()
+            .rev()
+            .fold(0, |modes, val| modes * 10 + val.mode_code());
+
+        let mut words = Vec::with_capacity(params.len() + 1);
+        words.push(W::from_isize(modes * 100 + self.code()));
+        words.extend(params.iter().map(|val| val.contents()));
+        words
+    }
 }
 
+/// formats the opcode the way the assembler reads it, e.g. `add [rb+1], 5, [10]`.
+impl<W: Word> fmt::Display for Opcode<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())?;
 
+        for (idx, val) in self.params().iter().enumerate() {
+            let separator = if idx == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, val)?;
+        }
 
-#[derive(Eq, PartialEq, Clone)]
-pub enum Value {
-    Position(isize),  //0
-    Immediate(isize), //1
-    Relative(isize),  //2
+        Ok(())
+    }
 }
 
-impl Value {
-    pub fn new(mem_contents: isize, mode_code: isize) -> Value {
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Value<W = isize> {
+    Position(W),  //0
+    Immediate(W), //1
+    Relative(W),  //2
+}
+
+impl<W: Word> Value<W> {
+    /// returns None if `mode_code` is not a known parameter mode.
+    pub fn new(mem_contents: W, mode_code: isize) -> Option<Value<W>> {
         match mode_code {
-            0 => Value::Position(mem_contents),
-            1 => Value::Immediate(mem_contents),
-            2 => Value::Relative(mem_contents),
-            _ => panic!("Unsupported parameter mode."),
+            0 => Some(Value::Position(mem_contents)),
+            1 => Some(Value::Immediate(mem_contents)),
+            2 => Some(Value::Relative(mem_contents)),
+            _ => None,
+        }
+    }
+
+    pub fn mode_code(&self) -> isize {
+        match self {
+            Value::Position(_) => 0,
+            Value::Immediate(_) => 1,
+            Value::Relative(_) => 2,
+        }
+    }
+
+    /// the memory word this value was parsed from.
+    pub fn contents(&self) -> W {
+        match *self {
+            Value::Position(contents) | Value::Immediate(contents) | Value::Relative(contents) => contents,
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Value<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Position(addr) => write!(f, "[{}]", addr),
+            Value::Immediate(imm) => write!(f, "{}", imm),
+            Value::Relative(rel_addr) if rel_addr == W::default() => write!(f, "[rb]"),
+            Value::Relative(rel_addr) if rel_addr < W::default() => write!(f, "[rb{}]", rel_addr),
+            Value::Relative(rel_addr) => write!(f, "[rb+{}]", rel_addr),
         }
     }
 }
 
+/// the state of the machine at the instruction that faulted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fault<W = isize> {
+    pub inst_ptr: isize,
+    /// the full instruction word, parameter modes included.
+    pub raw_opcode: W,
+    /// the relative base.
+    pub offset: isize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IntcodeError<W = isize> {
+    UnsupportedOpcode(Fault<W>),
+    /// holds the offending mode digit.
+    UnsupportedParameterMode(isize, Fault<W>),
+    /// holds the offending address.
+    NegativeAddress(isize, Fault<W>),
+    InputEmpty(Fault<W>),
+    /// a word used as an address, jump target or relative base adjustment doesn't fit in an isize,
+    /// or adding it to the relative base overflowed. holds the offending word, or the instruction
+    /// pointer if an instruction runs past the largest address.
+    AddressOutOfRange(W, Fault<W>),
+    /// the result of an Add or Mul doesn't fit in the word type, which reports overflow.
+    Overflow(Fault<W>),
+    /// an instruction was decoded with, or given, a different number of parameters than its arity.
+    WrongArity { code: isize, arity: usize, found: usize, fault: Fault<W> },
+}
+
+impl<W: Word> IntcodeError<W> {
+    pub fn fault(&self) -> Fault<W> {
+        match *self {
+            IntcodeError::UnsupportedOpcode(fault)
+            | IntcodeError::UnsupportedParameterMode(_, fault)
+            | IntcodeError::NegativeAddress(_, fault)
+            | IntcodeError::InputEmpty(fault)
+            | IntcodeError::AddressOutOfRange(_, fault)
+            | IntcodeError::Overflow(fault)
+            | IntcodeError::WrongArity { fault, .. } => fault,
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnsupportedOpcode(fault) => {
+                write!(f, "unsupported opcode {}", fault.raw_opcode)?
+            }
+            IntcodeError::UnsupportedParameterMode(mode, fault) => write!(
+                f,
+                "unsupported parameter mode {} in opcode {}",
+                mode, fault.raw_opcode
+            )?,
+            IntcodeError::NegativeAddress(addr, _) => {
+                write!(f, "attempt to access negative memory address {}", addr)?
+            }
+            IntcodeError::InputEmpty(_) => write!(f, "input is empty")?,
+            IntcodeError::AddressOutOfRange(word, _) => {
+                write!(f, "{} is out of range for an address", word)?
+            }
+            IntcodeError::Overflow(_) => write!(f, "arithmetic overflow")?,
+            IntcodeError::WrongArity { code, arity, found, .. } => write!(
+                f,
+                "opcode {} takes {} parameters, but was given {}",
+                code, arity, found
+            )?,
+        };
+
+        let fault = self.fault();
+        write!(
+            f,
+            " (instruction pointer {}, relative base {})",
+            fault.inst_ptr, fault.offset
+        )
+    }
+}
+
+impl<W: Word> Error for IntcodeError<W> {}
+
 #[derive(Clone)]
-pub struct IntcodeRunner {
-    mem: Vec<isize>,
-    extra_mem: HashMap<isize, isize>,
+pub struct IntcodeRunner<W = isize> {
+    mem: Memory<W>,
+    /// the decoded instructions of the Program the runner was spawned from, if any.
+    decoded: Option<Arc<Vec<Option<Opcode<W>>>>>,
+    /// one bit per cached instruction that has been written over.
+    code_dirty: Vec<u64>,
+    /// the compiled instructions of the CompiledProgram the runner was spawned from, if any.
+    compiled: Option<Arc<Vec<Option<CompiledOp<W>>>>>,
     inst_ptr: isize,
     offset: isize,
-    inputs: VecDeque<isize>,
+    inputs: VecDeque<W>,
     input_mode: InputMode,
-    output: Option<isize>,
+    output: Option<W>,
     halted: bool,
+    watchpoints: Vec<Watchpoint>,
+    watch_log: Vec<WatchHit<W>>,
+    /// a hit of a pausing watchpoint, not yet reported by step().
+    watch_pause: Option<WatchHit<W>>,
+    trace: Option<Trace<W>>,
+    session: Option<Session<W>>,
+    journal: Option<Journal<W>>,
+    limits: Limits,
+    /// the runner's own instruction set, or None for the standard one.
+    instructions: Option<Arc<InstructionSet<W>>>,
 }
 
+/// how the runner's own input queue is consumed. SingleInput behaves like devices::Repeat, which
+/// along with the other input sources can be attached with run_with() instead.
 #[derive(Clone)]
 pub enum InputMode {
     ConsumeInput,
     SingleInput,
 }
 
+/// the reason the runner stopped running.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RunState<W = isize> {
+    Halted,
+    /// an In instruction was reached with no input available. it has not been executed,
+    /// so pushing an input and running again resumes the program.
+    NeedsInput,
+    Output(W),
+    /// a watchpoint set to pause was hit by the last instruction, which has completed.
+    Watchpoint(WatchHit<W>),
+    /// the step budget or the deadline was exhausted before the next instruction, which has not
+    /// been executed. raising the limit and running again resumes the program.
+    LimitReached(Limit),
+}
+
 impl IntcodeRunner {
     pub fn new(mem: &[isize]) -> IntcodeRunner {
+        IntcodeRunner::from_words(mem)
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// like new(), for any word type.
+    pub fn from_words(mem: &[W]) -> IntcodeRunner<W> {
         IntcodeRunner {
-            mem: mem.to_vec(),
-            extra_mem: HashMap::new(),
+            mem: Memory::new(mem),
+            decoded: None,
+            code_dirty: Vec::new(),
+            compiled: None,
             inst_ptr: 0,
             offset: 0,
             inputs: VecDeque::new(),
             input_mode: InputMode::ConsumeInput,
             output: None,
             halted: false,
+            watchpoints: Vec::new(),
+            watch_log: Vec::new(),
+            watch_pause: None,
+            trace: None,
+            session: None,
+            journal: None,
+            limits: Limits::default(),
+            instructions: None,
         }
     }
 
     #[allow(dead_code)]
     pub fn print_mem(&mut self) {
-        dbg!(&self.mem);
-        dbg!(&self.extra_mem);
+        dbg!(&self.mem.to_vec());
+        dbg!(&self.mem.extra_words());
         println!(
             "input is {:?} and offset is {}",
             self.inputs.back(),
@@ -94,64 +397,132 @@ impl IntcodeRunner {
         );
     }
 
-    // unfortunately for now this has to take &mut self, because
-    // accessing uninitialized positive memory is legal and would
-    // require modifying the extra memory.
-    fn mem_at(&mut self, addr: isize) -> isize {
-        let mem_limit = self.mem.len() as isize;
+    /// the state of the machine, to be attached to an error raised by the current instruction.
+    fn fault(&self) -> Fault<W> {
+        self.fault_at(self.inst_ptr)
+    }
+
+    fn fault_at(&self, inst_ptr: isize) -> Fault<W> {
+        let raw_opcode = if inst_ptr < 0 { W::default() } else { self.read(inst_ptr as usize) };
 
-        match addr {
-            a if a < 0 => panic!("Attempt to access a negative memory address"),
-            a if a < mem_limit => self.mem[addr as usize],
-            _ => {
-                self.extra_mem.entry(addr).or_default(); //default is 0
-                self.extra_mem[&addr]
-            }
+        Fault {
+            inst_ptr,
+            raw_opcode,
+            offset: self.offset,
         }
     }
 
-    fn set_mem(&mut self, addr: isize, new_val: isize) {
-        let mem_limit = self.mem.len() as isize;
+    fn mem_at(&mut self, addr: isize) -> Result<W, IntcodeError<W>> {
+        let val = self.fetch(addr)?;
 
-        match addr {
-            a if a < 0 => panic!("Attempt to access a negative memory address"),
-            a if a < mem_limit => self.mem[addr as usize] = new_val,
-            _ => {
-                self.extra_mem.insert(addr, new_val);
-            }
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, Access::Read, val);
+        }
+
+        Ok(val)
+    }
+
+    // unlike mem_at(), this doesn't trigger watchpoints, so it's used for fetching instructions.
+    fn fetch(&self, addr: isize) -> Result<W, IntcodeError<W>> {
+        if addr < 0 {
+            return Err(IntcodeError::NegativeAddress(addr, self.fault()));
+        }
+
+        Ok(self.read(addr as usize))
+    }
+
+    fn set_mem(&mut self, addr: isize, new_val: W) -> Result<(), IntcodeError<W>> {
+        if addr < 0 {
+            return Err(IntcodeError::NegativeAddress(addr, self.fault()));
+        }
+
+        self.write(addr as usize, new_val);
+        self.trace_write(addr, new_val);
+
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, Access::Write, new_val);
         }
+
+        Ok(())
+    }
+
+    fn read(&self, addr: usize) -> W {
+        self.mem.get(addr)
+    }
+
+    fn write(&mut self, addr: usize, new_val: W) {
+        self.journal_write(addr);
+        self.mem.set(addr, new_val);
+        self.invalidate_code(addr);
     }
 
-    fn get_next_input(&mut self) -> isize {
-        match self.input_mode {
-            InputMode::ConsumeInput => self.inputs.pop_back().expect("Input is empty"),
-            InputMode::SingleInput => *self.inputs.back().expect("Input is empty"),
+    // the input is only consumed once it has been stored successfully,
+    // so that a failed In instruction leaves the inputs untouched.
+    fn peek_next_input(&self) -> Result<W, IntcodeError<W>> {
+        self.inputs
+            .back()
+            .copied()
+            .ok_or_else(|| IntcodeError::InputEmpty(self.fault()))
+    }
+
+    fn consume_input(&mut self) {
+        if let Some(&value) = self.inputs.back() {
+            let inst_ptr = self.inst_ptr;
+            self.record(Event::Input { inst_ptr, value });
+            self.journal_input(value);
+        }
+
+        if let InputMode::ConsumeInput = self.input_mode {
+            self.inputs.pop_back();
         }
     }
 
+    fn produce_output(&mut self, value: W) {
+        self.output = Some(value);
+
+        let inst_ptr = self.inst_ptr;
+        self.record(Event::Output { inst_ptr, value });
+    }
+
+    /// `word` as an address, or the like.
+    fn to_addr(&self, word: W) -> Result<isize, IntcodeError<W>> {
+        word.to_isize()
+            .ok_or_else(|| IntcodeError::AddressOutOfRange(word, self.fault()))
+    }
+
+    fn relative_addr(&self, rel_addr: W) -> Result<isize, IntcodeError<W>> {
+        self.offset
+            .checked_add(self.to_addr(rel_addr)?)
+            .ok_or_else(|| IntcodeError::AddressOutOfRange(rel_addr, self.fault()))
+    }
+
     //see: https://www.reddit.com/r/adventofcode/comments/e8aw9j/2019_day_9_part_1_how_to_fix_203_error/
-    pub fn eval_interpret(&mut self, val: Value) -> isize {
-        match val {
-            Value::Position(addr) => self.mem_at(addr),
+    pub fn eval_interpret(&mut self, val: Value<W>) -> Result<W, IntcodeError<W>> {
+        let res = match val {
+            Value::Position(addr) => self.mem_at(self.to_addr(addr)?)?,
             Value::Immediate(imm) => imm,
-            Value::Relative(rel_addr) => self.mem_at(self.offset + rel_addr),
-        }
+            Value::Relative(rel_addr) => self.mem_at(self.relative_addr(rel_addr)?)?,
+        };
+
+        self.trace_read(res);
+        Ok(res)
     }
 
-    pub fn eval_literal(&mut self, val: Value) -> isize {
+    pub fn eval_literal(&mut self, val: Value<W>) -> Result<isize, IntcodeError<W>> {
         match val {
-            Value::Position(addr) => addr,
-            Value::Immediate(imm) => imm, //should probably panic!() here
-            Value::Relative(rel_addr) => self.offset + rel_addr,
+            Value::Position(addr) => self.to_addr(addr),
+            //immediate mode writes go to the address given. analysis::Cfg flags them
+            Value::Immediate(imm) => self.to_addr(imm),
+            Value::Relative(rel_addr) => self.relative_addr(rel_addr),
         }
     }
 
 
-    pub fn push_input_front(&mut self, input: isize) {
+    pub fn push_input_front(&mut self, input: W) {
         self.inputs.push_front(input);
     }
 
-    pub fn push_input(&mut self, input: isize) {
+    pub fn push_input(&mut self, input: W) {
         self.inputs.push_back(input);
     }
 
@@ -159,141 +530,205 @@ impl IntcodeRunner {
         self.input_mode = mode;
     }
 
-    pub fn output(&self) -> Option<isize> {
+    /// the most recent output.
+    pub fn output(&self) -> Option<W> {
         self.output
     }
 
-    pub fn value_at_pos_0(&mut self) -> isize {
-        self.mem_at(0)
+    /// lazily runs the program, yielding each output as it is produced.
+    /// the iterator ends once the program halts, needs input, hits a pausing watchpoint or reaches
+    /// a limit,
+    /// or after yielding a fault.
+    pub fn outputs(&mut self) -> Outputs<'_, W> {
+        Outputs {
+            runner: self,
+            done: false,
+        }
     }
 
-    pub fn set_noun(&mut self, noun: isize) {
-        self.set_mem(1, noun);
+    /// like outputs(), but yields the outputs in chunks of `chunk_size`,
+    /// e.g. chunks of 3 for (x, y, tile) triples.
+    /// if the program stops in the middle of a chunk, the shorter chunk is yielded last.
+    /// if it faults in the middle of one, the shorter chunk is yielded before the fault.
+    pub fn outputs_chunked(&mut self, chunk_size: usize) -> OutputsChunked<'_, W> {
+        assert!(chunk_size > 0, "chunk size must be positive");
+
+        OutputsChunked {
+            outputs: self.outputs(),
+            chunk_size,
+            fault: None,
+        }
     }
 
-    pub fn set_verb(&mut self, verb: isize) {
-        self.set_mem(2, verb);
+    pub fn value_at_pos_0(&self) -> W {
+        self.read(0)
+    }
+
+    pub fn set_noun(&mut self, noun: W) {
+        self.write(1, noun);
+    }
+
+    pub fn set_verb(&mut self, verb: W) {
+        self.write(2, verb);
     }
 
     pub fn has_halted(&self) -> bool {
         self.halted
     }
 
-    fn reboot(&mut self) {
-        self.inst_ptr = 0;
-        self.offset = 0;
-        self.inputs.clear();
-        self.extra_mem.clear();
+    /// lets a halted program run on from the instruction after its Halt.
+    pub fn resume_after_halt(&mut self) {
         self.halted = false;
     }
 
-    pub fn parse_cur_opcode(&mut self) -> Opcode {
-        let ptr = self.inst_ptr;
-        let code = self.mem_at(ptr);
-        let two_digit_opcode = code % 100;
-        let (mode1, mode2, mode3) = ((code / 100) % 10, (code / 1000) % 10, (code / 10000) % 10);
-
-        let val1 = Value::new(self.mem_at(ptr + 1), mode1);
-        let val2 = Value::new(self.mem_at(ptr + 2), mode2);
-        let val3 = Value::new(self.mem_at(ptr + 3), mode3);
+    pub fn inst_ptr(&self) -> isize {
+        self.inst_ptr
+    }
 
-        match two_digit_opcode {
-            1 => Opcode::Add(val1, val2, val3),
+    /// the relative base.
+    pub fn offset(&self) -> isize {
+        self.offset
+    }
 
-            2 => Opcode::Mul(val1, val2, val3),
+    pub fn parse_cur_opcode(&self) -> Result<Opcode<W>, IntcodeError<W>> {
+        if let Some(opcode) = self.cached_opcode(self.inst_ptr) {
+            return Ok(opcode);
+        }
 
-            3 => Opcode::In(val1),
+        self.decode_at(self.inst_ptr)
+    }
 
-            4 => Opcode::Out(val1),
+    /// parses the instruction at `ptr`, which doesn't have to be the current instruction.
+    pub fn decode_at(&self, ptr: isize) -> Result<Opcode<W>, IntcodeError<W>> {
+        let code = match self.fetch(ptr)?.to_isize() {
+            Some(code) => code,
+            None => return Err(IntcodeError::UnsupportedOpcode(self.fault_at(ptr))),
+        };
+        let two_digit_opcode = code % 100;
 
-            5 => Opcode::JumpIfTrue(val1, val2),
+        let instruction = match &self.instructions {
+            Some(instructions) => instructions.get(two_digit_opcode),
+            None => instruction::standard(two_digit_opcode),
+        };
+        let instruction = instruction.ok_or_else(|| IntcodeError::UnsupportedOpcode(self.fault_at(ptr)))?;
+        let arity = instruction.arity();
+
+        //only the instruction's own parameters are parsed, the rest are passed on as immediate zeroes
+        let mut vals: [Value<W>; 3] = std::array::from_fn(|_| Value::Immediate(W::default()));
+        let mut modes = code / 100;
+        for (idx, val) in vals.iter_mut().enumerate().take(arity) {
+            *val = self.parse_value(ptr, idx as isize + 1, modes % 10)?;
+            modes /= 10;
+        }
 
-            6 => Opcode::JumpIfFalse(val1, val2),
+        let opcode = instruction.decode(vals);
+        if opcode.num_vals() != arity as isize {
+            return Err(IntcodeError::WrongArity {
+                code: two_digit_opcode,
+                arity,
+                found: opcode.num_vals() as usize,
+                fault: self.fault_at(ptr),
+            });
+        }
 
-            7 => Opcode::LT(val1, val2, val3),
+        Ok(opcode)
+    }
 
-            8 => Opcode::EQ(val1, val2, val3),
+    fn parse_value(&self, ptr: isize, param_idx: isize, mode_code: isize) -> Result<Value<W>, IntcodeError<W>> {
+        let addr = ptr
+            .checked_add(param_idx)
+            .ok_or_else(|| IntcodeError::AddressOutOfRange(W::from_isize(ptr), self.fault_at(ptr)))?;
+        let mem_contents = self.fetch(addr)?;
 
-            9 => Opcode::BaseOffset(val1),
+        Value::new(mem_contents, mode_code)
+            .ok_or_else(|| IntcodeError::UnsupportedParameterMode(mode_code, self.fault_at(ptr)))
+    }
 
-            99 => Opcode::Halt,
+    //executes opcode and returns the state it leaves the runner in, if it's worth stopping for.
+    //on error, the instruction pointer is left at the faulting instruction.
+    pub fn exec_opcode(&mut self, opcode: Opcode<W>) -> Result<Option<RunState<W>>, IntcodeError<W>> {
+        let next_inst_ptr = self
+            .inst_ptr
+            .checked_add(opcode.num_vals() + 1)
+            .ok_or_else(|| IntcodeError::AddressOutOfRange(W::from_isize(self.inst_ptr), self.fault()))?;
+
+        let (next_inst_ptr, run_state) = match instruction::exec(self, &opcode)? {
+            Flow::Next => (next_inst_ptr, None),
+            Flow::Jump(target) => (target, None),
+            Flow::Report(run_state) => (next_inst_ptr, Some(run_state)),
+        };
 
-            0 => Opcode::Reboot,
+        self.inst_ptr = next_inst_ptr;
 
-            _ => panic!("Unsupported opcode: {}", code),
-        }
+        Ok(run_state)
     }
 
-    //executes opcode and returns true iff an Out instruction was executed
-    pub fn exec_opcode(&mut self, opcode: Opcode) -> bool {
-        self.inst_ptr += &opcode.num_vals() + 1;
-        let mut got_new_output = false;
-
-        match opcode {
-            Opcode::Add(val1, val2, val3) => {
-                let op1 = self.eval_interpret(val1);
-                let op2 = self.eval_interpret(val2);
-                let addr = self.eval_literal(val3);
-                self.set_mem(addr, op1 + op2);
-            }
+    /// executes a single instruction, and returns the state it leaves the runner in
+    /// if it halted, needs input or produced an output.
+    /// once the program has halted, nothing more is executed and Halted is returned every time.
+    pub fn step(&mut self) -> Result<Option<RunState<W>>, IntcodeError<W>> {
+        //a watchpoint hit by an instruction that also had something else to report
+        if let Some(hit) = self.watch_pause.take() {
+            return Ok(Some(RunState::Watchpoint(hit)));
+        }
 
-            Opcode::Mul(val1, val2, val3) => {
-                let op1 = self.eval_interpret(val1);
-                let op2 = self.eval_interpret(val2);
-                let addr = self.eval_literal(val3);
-                self.set_mem(addr, op1 * op2);
-            }
+        if self.halted {
+            return Ok(Some(RunState::Halted));
+        }
 
-            Opcode::In(val) => {
-                let input = self.get_next_input();
-                let addr = self.eval_literal(val);
-                self.set_mem(addr, input);
+        if let Some(limit) = self.limit_reached() {
+            return Ok(Some(RunState::LimitReached(limit)));
+        }
 
-            }
+        self.journal_begin();
+        let res = self.exec_step();
 
-            Opcode::Out(val) => {
+        let completed = !matches!(res, Err(_) | Ok(Some(RunState::NeedsInput)));
+        self.journal_end(completed);
+        if completed {
+            self.charge_step();
+        }
 
-                self.output = Some(self.eval_interpret(val));
-                got_new_output = true;
-            }
+        res
+    }
 
-            Opcode::JumpIfTrue(val1, val2) => {
-                if self.eval_interpret(val1) != 0 {
-                    self.inst_ptr = self.eval_interpret(val2);
-                }
-            }
+    fn exec_step(&mut self) -> Result<Option<RunState<W>>, IntcodeError<W>> {
+        if let Some(res) = self.step_compiled() {
+            return match res {
+                Err(IntcodeError::InputEmpty(_)) => Ok(Some(RunState::NeedsInput)),
+                res => res,
+            };
+        }
 
-            Opcode::JumpIfFalse(val1, val2) => {
-                if self.eval_interpret(val1) == 0 {
-                    self.inst_ptr = self.eval_interpret(val2);
-                }
-            }
+        let cur_opcode = self.parse_cur_opcode()?;
 
-            Opcode::LT(val1, val2, val3) => {
-                let comparison_res = (self.eval_interpret(val1) < self.eval_interpret(val2)) as isize;
-                let addr = self.eval_literal(val3);
-                self.set_mem(addr, comparison_res);
-            }
+        self.trace_begin(&cur_opcode);
+        let res = self.exec_opcode(cur_opcode);
+        self.trace_end(res.is_ok());
 
-            Opcode::EQ(val1, val2, val3) => {
-                let comparison_res = (self.eval_interpret(val1) == self.eval_interpret(val2)) as isize;
-                let addr = self.eval_literal(val3);
-                self.set_mem(addr, comparison_res);
+        match res {
+            Err(IntcodeError::InputEmpty(_)) => Ok(Some(RunState::NeedsInput)),
+            Err(e) => {
+                self.watch_pause = None;
+                Err(e)
             }
+            Ok(None) => Ok(self.watch_pause.take().map(RunState::Watchpoint)),
+            res => res,
+        }
+    }
 
-            Opcode::BaseOffset(val) => {
-                let offset_change = self.eval_interpret(val);
-                self.offset += offset_change;
+    /// runs the program until it halts, needs input, produces an output, hits a pausing watchpoint
+    /// or reaches a limit.
+    pub fn run(&mut self) -> Result<RunState<W>, IntcodeError<W>> {
+        loop {
+            if let Some(run_state) = self.step()? {
+                return Ok(run_state);
             }
-
-            Opcode::Halt => self.halted = true,
-
-            Opcode::Reboot => self.reboot(),
-        };
-
-        got_new_output
+        }
     }
+}
 
+impl IntcodeRunner {
     pub fn ask_for_input(lookup_table: &HashMap<char, isize>) -> Result<isize, &'static str> {
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
@@ -312,4 +747,126 @@ impl IntcodeRunner {
             Err(_error) => panic!("failed to read input, got {:?}", _error),
         }
     }
-}
\ No newline at end of file
+}
+pub struct Outputs<'a, W = isize> {
+    runner: &'a mut IntcodeRunner<W>,
+    done: bool,
+}
+
+impl<W: Word> Iterator for Outputs<'_, W> {
+    type Item = Result<W, IntcodeError<W>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.runner.has_halted() {
+            return None;
+        }
+
+        match self.runner.run() {
+            Ok(RunState::Output(output)) => Some(Ok(output)),
+            Ok(_) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+pub struct OutputsChunked<'a, W = isize> {
+    outputs: Outputs<'a, W>,
+    chunk_size: usize,
+    /// a fault held back while the chunk before it was yielded.
+    fault: Option<IntcodeError<W>>,
+}
+
+impl<W: Word> Iterator for OutputsChunked<'_, W> {
+    type Item = Result<Vec<W>, IntcodeError<W>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.fault.take() {
+            return Some(Err(e));
+        }
+
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+
+        while chunk.len() < self.chunk_size {
+            match self.outputs.next() {
+                Some(Ok(output)) => chunk.push(output),
+                Some(Err(e)) if chunk.is_empty() => return Some(Err(e)),
+                Some(Err(e)) => {
+                    self.fault = Some(e);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+#[test]
+fn reports_bad_addresses_and_modes() {
+    //adds [-1] to itself
+    let mut runner = IntcodeRunner::new(&[1, -1, -1, 0, 99]);
+    assert!(matches!(runner.run(), Err(IntcodeError::NegativeAddress(-1, _))));
+
+    //mode digit 3 on the first parameter
+    let mut runner = IntcodeRunner::new(&[304, 0, 99]);
+    assert!(matches!(runner.run(), Err(IntcodeError::UnsupportedParameterMode(3, _))));
+    assert_eq!(runner.inst_ptr(), 0);
+
+    //writes an Out instruction to the largest address and jumps to it, so its parameter would be
+    //past the largest address
+    let mut runner = IntcodeRunner::new(&[1101, 104, 0, isize::MAX, 1105, 1, isize::MAX]);
+    let err = runner.run().unwrap_err();
+    assert!(matches!(err, IntcodeError::AddressOutOfRange(_, _)));
+    assert_eq!(err.fault().inst_ptr, isize::MAX);
+}
+
+#[test]
+fn waits_for_missing_input() {
+    let mut runner = IntcodeRunner::new(&[3, 0, 4, 0, 99]);
+    assert_eq!(runner.run(), Ok(RunState::NeedsInput));
+    assert_eq!(runner.inst_ptr(), 0);
+
+    //executing the In instruction directly reports the empty queue
+    assert!(matches!(
+        runner.exec_opcode(Opcode::In(Value::Position(0))),
+        Err(IntcodeError::InputEmpty(_))
+    ));
+
+    runner.push_input(7);
+    assert_eq!(runner.run(), Ok(RunState::Output(7)));
+}
+
+#[test]
+fn stays_halted() {
+    let mut runner = IntcodeRunner::new(&[99, 104, 5, 99]);
+    assert_eq!(runner.run(), Ok(RunState::Halted));
+    assert_eq!(runner.run(), Ok(RunState::Halted));
+    assert_eq!(runner.step(), Ok(Some(RunState::Halted)));
+    assert_eq!(runner.output(), None);
+
+    runner.resume_after_halt();
+    assert_eq!(runner.run(), Ok(RunState::Output(5)));
+}
+
+#[test]
+fn keeps_chunk_before_fault() {
+    //outputs 1, 2 and 3, then hits opcode 55
+    let mut runner = IntcodeRunner::new(&[104, 1, 104, 2, 104, 3, 55]);
+    let mut chunks = runner.outputs_chunked(2);
+
+    assert_eq!(chunks.next(), Some(Ok(vec![1, 2])));
+    assert_eq!(chunks.next(), Some(Ok(vec![3])));
+    assert!(matches!(chunks.next(), Some(Err(IntcodeError::UnsupportedOpcode(_)))));
+    assert_eq!(chunks.next(), None);
+}
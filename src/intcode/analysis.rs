@@ -0,0 +1,480 @@
+//! static analysis of a memory image: basic blocks, the control-flow graph between them and
+//! warnings about suspicious instructions.
+//!
+//! code is found the same way the disassembler finds it, with the InstructionSet given. blocks
+//! start at address 0, at jump targets, after jumps and halts, and at return addresses the
+//! program pushes. Cfg::dot renders the graph for Graphviz.
+//!
+//! calls and returns are recognised by following the relative base, which programs use as a stack
+//! pointer: a call pushes an immediate return address to `[rb+k]` and jumps to the function, and
+//! the function returns by jumping through that same stack slot once `arb` has moved the relative
+//! base back. the relative base is tracked as an offset from its value when the current function
+//! was entered, so this works however deep the stack is.
+//!
+//! two kinds of instructions are flagged:
+//!
+//! * writes to code, i.e. self-modifying code. only writes to fixed addresses can be checked.
+//! * writes through an immediate mode parameter, which the VM treats like position mode.
+
+use crate::intcode::disassembler::{code_pointers, find_code, successors};
+use crate::intcode::{InstructionSet, Opcode, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum EdgeKind {
+    /// falling through to the next block, including conditional jumps that aren't taken.
+    Next,
+    Jump,
+    Call,
+    Return,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Edge {
+    pub from: isize,
+    pub to: isize,
+    pub kind: EdgeKind,
+}
+
+pub struct BasicBlock {
+    pub start: isize,
+    pub instructions: Vec<(isize, Opcode)>,
+    /// the entry of the function the block was first reached from, if it was reached at all.
+    pub function: Option<isize>,
+    /// the relative base on entry to the block, as an offset from its value when the function was
+    /// entered. None if it can't be determined statically.
+    pub base: Option<isize>,
+}
+
+impl BasicBlock {
+    /// the address just past the end of the block.
+    pub fn end(&self) -> isize {
+        let (addr, opcode) = self.terminator();
+        addr + opcode.params().len() as isize + 1
+    }
+
+    fn terminator(&self) -> &(isize, Opcode) {
+        //blocks are never empty
+        self.instructions.last().unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Warning {
+    /// the instruction at `at` writes to `target`, which holds code.
+    SelfModifying { at: isize, target: isize },
+    /// the instruction at `at` writes through an immediate mode parameter.
+    ImmediateWrite { at: isize },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::SelfModifying { at, target } => {
+                write!(f, "the instruction at {} writes to code at {}", at, target)
+            }
+            Warning::ImmediateWrite { at } => {
+                write!(f, "the instruction at {} writes in immediate mode", at)
+            }
+        }
+    }
+}
+
+/// a call site, found by its pushed return address.
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+struct Call {
+    callee: isize,
+    ret: isize,
+    /// where the return address is, relative to the relative base the callee is entered with.
+    slot: isize,
+}
+
+fn is_jump(opcode: &Opcode) -> bool {
+    matches!(opcode, Opcode::JumpIfTrue(_, _) | Opcode::JumpIfFalse(_, _))
+}
+
+fn ends_block(opcode: &Opcode) -> bool {
+    is_jump(opcode) || matches!(opcode, Opcode::Halt | Opcode::Extension(_))
+}
+
+/// the parameter an instruction writes to, if any.
+fn write_param(opcode: &Opcode) -> Option<&Value> {
+    match opcode {
+        Opcode::Add(_, _, val) | Opcode::Mul(_, _, val) | Opcode::LT(_, _, val) | Opcode::EQ(_, _, val) => {
+            Some(val)
+        }
+        Opcode::In(val) => Some(val),
+        _ => None,
+    }
+}
+
+fn split_blocks(instructions: &BTreeMap<isize, Opcode>) -> BTreeMap<isize, BasicBlock> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+
+    for (&addr, opcode) in instructions {
+        if ends_block(opcode) {
+            leaders.insert(addr + opcode.params().len() as isize + 1);
+            leaders.extend(successors(addr, opcode));
+        }
+
+        leaders.extend(code_pointers(opcode));
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut cur: Option<BasicBlock> = None;
+
+    for (&addr, opcode) in instructions {
+        let continues = cur
+            .as_ref()
+            .is_some_and(|block| block.end() == addr && !leaders.contains(&addr));
+
+        if !continues {
+            if let Some(block) = cur.take() {
+                blocks.insert(block.start, block);
+            }
+
+            cur = Some(BasicBlock {
+                start: addr,
+                instructions: Vec::new(),
+                function: None,
+                base: None,
+            });
+        }
+
+        if let Some(block) = cur.as_mut() {
+            block.instructions.push((addr, opcode.clone()));
+        }
+    }
+
+    if let Some(block) = cur {
+        blocks.insert(block.start, block);
+    }
+
+    blocks
+}
+
+/// the edges a block's last instruction statically leads to, before calls and returns are known.
+fn static_edges(block: &BasicBlock) -> Vec<Edge> {
+    let (addr, opcode) = block.terminator();
+    let next = block.end();
+
+    let targets = if ends_block(opcode) {
+        successors(*addr, opcode)
+    } else {
+        vec![next]
+    };
+
+    targets
+        .into_iter()
+        .map(|to| Edge {
+            from: block.start,
+            to,
+            kind: if to == next { EdgeKind::Next } else { EdgeKind::Jump },
+        })
+        .collect()
+}
+
+/// follows the relative base through a block, starting from `base`. returns the relative base at
+/// the end of the block, along with the last return address pushed to the stack and its slot,
+/// relative to the function's entry base. only addresses that start a block can be return
+/// addresses, since pointers to data are pushed as arguments the same way. a base that overflows
+/// is unknown.
+fn walk(
+    block: &BasicBlock,
+    mut base: Option<isize>,
+    blocks: &BTreeMap<isize, BasicBlock>,
+) -> (Option<isize>, Option<(isize, isize)>) {
+    let mut pushed = None;
+
+    for (_, opcode) in &block.instructions {
+        if let (Some(&Value::Relative(rel_addr)), Some(base)) = (write_param(opcode), base) {
+            if let Some(&ret) = code_pointers(opcode).iter().find(|ret| blocks.contains_key(ret)) {
+                if let Some(slot) = base.checked_add(rel_addr) {
+                    pushed = Some((ret, slot));
+                }
+            }
+        }
+
+        if let Opcode::BaseOffset(val) = opcode {
+            base = match *val {
+                Value::Immediate(change) => base.and_then(|base| base.checked_add(change)),
+                _ => None,
+            };
+        }
+    }
+
+    (base, pushed)
+}
+
+pub struct Cfg {
+    blocks: BTreeMap<isize, BasicBlock>,
+    edges: Vec<Edge>,
+    functions: BTreeSet<isize>,
+    warnings: Vec<Warning>,
+}
+
+impl Cfg {
+    pub fn new(mem: &[isize], instructions: &InstructionSet) -> Cfg {
+        let instructions = find_code(mem, instructions);
+        let mut blocks = split_blocks(&instructions);
+
+        let mut edges = BTreeSet::new();
+        let mut functions = BTreeSet::new();
+        let mut calls = BTreeSet::new();
+        //blocks that jump through the stack, with their function and the slot they jump through
+        let mut returns = BTreeMap::new();
+
+        functions.insert(0);
+        let mut to_visit = vec![(0, 0, Some(0))];
+
+        while let Some((start, function, base)) = to_visit.pop() {
+            let block = match blocks.get_mut(&start) {
+                Some(block) => block,
+                None => continue,
+            };
+
+            match block.function {
+                None => {
+                    block.function = Some(function);
+                    block.base = base;
+                }
+                //reached with conflicting relative bases, so it's unknown from here on
+                Some(_) if block.base.is_some() && block.base != base => block.base = None,
+                Some(_) => continue,
+            }
+
+            let block = &blocks[&start];
+            let (end_base, pushed) = walk(block, block.base, &blocks);
+            let (_, opcode) = block.terminator();
+
+            let target = match opcode {
+                Opcode::JumpIfTrue(_, target) | Opcode::JumpIfFalse(_, target) => Some(target),
+                _ => None,
+            };
+
+            returns.remove(&start);
+            let mut block_edges = static_edges(block);
+
+            //the pushed return address, with its slot relative to the base the callee starts with
+            let pushed = pushed
+                .zip(end_base)
+                .and_then(|((ret, slot), end_base)| Some((ret, slot.checked_sub(end_base)?)));
+
+            match (target, pushed, end_base) {
+                (Some(&Value::Immediate(callee)), Some((ret, slot)), Some(end_base)) => {
+                    functions.insert(callee);
+                    calls.insert(Call { callee, ret, slot });
+
+                    for edge in &mut block_edges {
+                        if edge.to == callee {
+                            edge.kind = EdgeKind::Call;
+                        }
+                    }
+
+                    //the callee is assumed to restore the relative base before returning
+                    to_visit.push((callee, callee, Some(0)));
+                    to_visit.push((ret, function, Some(end_base)));
+                }
+
+                (Some(&Value::Relative(rel_addr)), _, Some(end_base)) => {
+                    if let Some(slot) = end_base.checked_add(rel_addr) {
+                        returns.insert(start, (function, slot));
+                    }
+                }
+
+                _ => {}
+            }
+
+            for edge in block_edges {
+                if edge.kind != EdgeKind::Call {
+                    to_visit.push((edge.to, function, end_base));
+                }
+
+                edges.insert(edge);
+            }
+        }
+
+        for (&from, &(function, slot)) in &returns {
+            let rets = calls
+                .iter()
+                .filter(|call| call.callee == function && call.slot == slot)
+                .map(|call| call.ret);
+
+            edges.extend(rets.map(|to| Edge {
+                from,
+                to,
+                kind: EdgeKind::Return,
+            }));
+        }
+
+        let edges = edges.into_iter().filter(|edge| blocks.contains_key(&edge.to)).collect();
+
+        Cfg {
+            warnings: find_warnings(mem, &instructions),
+            blocks,
+            edges,
+            functions,
+        }
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.values()
+    }
+
+    /// the block starting at `start`.
+    pub fn block(&self, start: isize) -> Option<&BasicBlock> {
+        self.blocks.get(&start)
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// the edges leaving the block starting at `start`.
+    pub fn edges_from(&self, start: isize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == start)
+    }
+
+    /// the entries of the program and every function it calls.
+    pub fn functions(&self) -> &BTreeSet<isize> {
+        &self.functions
+    }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// the graph in Graphviz's DOT format.
+    pub fn dot(&self) -> Dot<'_> {
+        Dot(self)
+    }
+}
+
+fn find_warnings(mem: &[isize], instructions: &BTreeMap<isize, Opcode>) -> Vec<Warning> {
+    let mut is_code = vec![false; mem.len()];
+    for (&addr, opcode) in instructions {
+        for a in addr..=addr + opcode.params().len() as isize {
+            is_code[a as usize] = true;
+        }
+    }
+
+    let mut warnings = Vec::new();
+
+    for (&at, opcode) in instructions {
+        let target = match write_param(opcode) {
+            Some(&Value::Immediate(target)) => {
+                warnings.push(Warning::ImmediateWrite { at });
+                target
+            }
+            Some(&Value::Position(target)) => target,
+            _ => continue,
+        };
+
+        if target >= 0 && is_code.get(target as usize) == Some(&true) {
+            warnings.push(Warning::SelfModifying { at, target });
+        }
+    }
+
+    warnings
+}
+
+pub struct Dot<'a>(&'a Cfg);
+
+impl fmt::Display for Dot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cfg = self.0;
+
+        writeln!(f, "digraph intcode {{")?;
+        writeln!(f, "    node [shape=box, fontname=monospace];")?;
+
+        for block in cfg.blocks() {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(addr, opcode)| format!("{}: {}\\l", addr, opcode))
+                .collect();
+
+            let style = if cfg.functions.contains(&block.start) { ", style=bold" } else { "" };
+            writeln!(f, "    b{} [label=\"{}\"{}];", block.start, label, style)?;
+        }
+
+        for edge in cfg.edges() {
+            let attrs = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [color=blue]",
+                EdgeKind::Call => " [style=dashed, label=call]",
+                EdgeKind::Return => " [style=dotted, label=ret]",
+            };
+
+            writeln!(f, "    b{} -> b{}{};", edge.from, edge.to, attrs)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+#[test]
+fn finds_calls_and_returns() {
+    //calls a function that doubles its input twice, then outputs the result
+    let src = "
+            arb @stack
+            in [rb+1]
+            add @ret1, 0, [rb]
+            jt 1, @double
+    ret1:   add @ret2, 0, [rb]
+            jt 1, @double
+    ret2:   out [rb+1]
+            halt
+    double: arb 2
+            mul [rb-1], 2, [rb-1]
+            arb -2
+            jf 0, [rb]
+    stack:  data 0, 0
+    ";
+    let mem = crate::intcode::assembler::assemble(src).unwrap();
+    let cfg = Cfg::new(&mem, &InstructionSet::standard());
+
+    let starts: Vec<isize> = cfg.blocks().map(|block| block.start).collect();
+    assert_eq!(starts, vec![0, 11, 18, 21]);
+    assert_eq!(cfg.functions().iter().copied().collect::<Vec<_>>(), vec![0, 21]);
+    assert_eq!(cfg.block(11).unwrap().base, Some(32));
+
+    let from_double: Vec<Edge> = cfg.edges_from(21).copied().collect();
+    let ret = |to| Edge {
+        from: 21,
+        to,
+        kind: EdgeKind::Return,
+    };
+    assert_eq!(from_double, vec![ret(11), ret(18)]);
+
+    assert!(cfg.dot().to_string().contains("    b0 -> b21 [style=dashed, label=call];\n"));
+    assert!(cfg.warnings().is_empty());
+}
+
+#[test]
+fn flags_suspicious_writes() {
+    //adds into address 12 in immediate mode, then increments the instruction at 4
+    let mem = vec![11101, 0, 0, 12, 1001, 4, 1, 4, 99];
+    let cfg = Cfg::new(&mem, &InstructionSet::standard());
+
+    assert_eq!(
+        cfg.warnings(),
+        &[
+            Warning::ImmediateWrite { at: 0 },
+            Warning::SelfModifying { at: 4, target: 4 }
+        ]
+    );
+}
+
+#[test]
+fn unknown_base_on_overflow() {
+    let cfg = Cfg::new(&[109, isize::MAX, 109, isize::MAX, 99], &InstructionSet::standard());
+    assert_eq!(cfg.block(0).unwrap().base, Some(0));
+    assert_eq!(cfg.blocks().count(), 1);
+
+    //jumps through the stack with the relative base at its largest
+    let cfg = Cfg::new(&[109, isize::MAX, 2106, 0, 1, 99], &InstructionSet::standard());
+    assert!(cfg.edges_from(0).next().is_none());
+}
@@ -0,0 +1,262 @@
+//! text-mode programs, which read and write ASCII, one character per word.
+//!
+//! an AsciiIntcode takes input a line at a time, and yields output a line at a time, without the
+//! newline. outputs that aren't ASCII, like the amount of dust in day17, are passed through as is.
+//! a line the program hasn't finished when it stops is yielded as it is, before the fault if it
+//! faulted.
+
+use crate::intcode::devices::Ascii;
+use crate::intcode::{InputSource, IntcodeError, IntcodeRunner, RunState, Word};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsciiOutput<W = isize> {
+    /// a line of text, without its newline.
+    Line(String),
+    /// an output outside 0-127, which isn't a character.
+    Value(W),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsciiError<W = isize> {
+    Fault(IntcodeError<W>),
+    /// holds an output that isn't a character, where only lines were expected.
+    NotAscii(W),
+}
+
+impl<W: Word> From<IntcodeError<W>> for AsciiError<W> {
+    fn from(error: IntcodeError<W>) -> AsciiError<W> {
+        AsciiError::Fault(error)
+    }
+}
+
+impl<W: Word> fmt::Display for AsciiError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiError::Fault(error) => write!(f, "{}", error),
+            AsciiError::NotAscii(word) => write!(f, "output {}, which isn't ASCII", word),
+        }
+    }
+}
+
+impl<W: Word> Error for AsciiError<W> {}
+
+pub struct AsciiIntcode<W = isize> {
+    runner: IntcodeRunner<W>,
+    input: Ascii,
+    /// the line being output.
+    line: String,
+    /// outputs held back while the line before them was yielded.
+    pending: VecDeque<AsciiOutput<W>>,
+    /// why the program stopped running, once it has.
+    stopped: Option<Result<RunState<W>, IntcodeError<W>>>,
+}
+
+/// `word` as a character, if it's ASCII.
+fn to_char<W: Word>(word: W) -> Option<char> {
+    word.to_isize()
+        .filter(|&code| (0..=127).contains(&code))
+        .map(|code| code as u8 as char)
+}
+
+impl<W: Word> AsciiIntcode<W> {
+    pub fn new(runner: IntcodeRunner<W>) -> AsciiIntcode<W> {
+        AsciiIntcode {
+            runner,
+            input: Ascii::new(""),
+            line: String::new(),
+            pending: VecDeque::new(),
+            stopped: None,
+        }
+    }
+
+    pub fn runner(&self) -> &IntcodeRunner<W> {
+        &self.runner
+    }
+
+    /// the runner, to raise a limit or change watchpoints before calling resume().
+    pub fn runner_mut(&mut self) -> &mut IntcodeRunner<W> {
+        &mut self.runner
+    }
+
+    pub fn into_runner(self) -> IntcodeRunner<W> {
+        self.runner
+    }
+
+    /// queues a line of input, adding the newline. a program waiting for input resumes once the
+    /// next output is asked for.
+    pub fn send_line(&mut self, line: &str) {
+        self.input.push_str(line);
+        self.input.push_str("\n");
+
+        if let Some(Ok(RunState::NeedsInput)) = self.stopped {
+            self.stopped = None;
+        }
+    }
+
+    /// lets a program that hit a pausing watchpoint or a limit run on once the next output is
+    /// asked for, like send_line() does for one waiting for input.
+    pub fn resume(&mut self) {
+        if let Some(Ok(RunState::Watchpoint(_) | RunState::LimitReached(_))) = self.stopped {
+            self.stopped = None;
+        }
+    }
+
+    /// why the program stopped running: it halted, needs input that hasn't been sent, hit a pausing
+    /// watchpoint or a limit, or faulted. None while it can still run. a program waiting for input
+    /// runs on after send_line(), and one that hit a watchpoint or a limit after resume().
+    pub fn stopped(&self) -> Option<Result<RunState<W>, IntcodeError<W>>> {
+        self.stopped
+    }
+
+    /// the next line or value, or None once the program stopped running.
+    pub fn next_output(&mut self) -> Result<Option<AsciiOutput<W>>, IntcodeError<W>> {
+        if let Some(output) = self.pending.pop_front() {
+            return Ok(Some(output));
+        }
+
+        if let Some(stopped) = self.stopped {
+            return stopped.map(|_| None);
+        }
+
+        let stopped = loop {
+            let run_state = match self.runner.run() {
+                Ok(run_state) => run_state,
+                Err(e) => {
+                    self.stopped = Some(Err(e));
+
+                    //the unfinished line comes first, and the fault with the next call
+                    if self.line.is_empty() {
+                        return Err(e);
+                    }
+                    return Ok(Some(self.take_line()));
+                }
+            };
+
+            match run_state {
+                RunState::Output(word) => match to_char(word) {
+                    Some('\n') => return Ok(Some(self.take_line())),
+                    Some(c) => self.line.push(c),
+                    None if self.line.is_empty() => return Ok(Some(AsciiOutput::Value(word))),
+                    None => {
+                        self.pending.push_back(AsciiOutput::Value(word));
+                        return Ok(Some(self.take_line()));
+                    }
+                },
+
+                RunState::NeedsInput => match self.input.next_input() {
+                    Some(input) => self.runner.push_input(input),
+                    None => break run_state,
+                },
+
+                _ => break run_state,
+            }
+        };
+
+        self.stopped = Some(Ok(stopped));
+
+        if self.line.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.take_line()))
+        }
+    }
+
+    /// the lines output until the program stops. stops at an output that isn't a character, which
+    /// can still be read with next_output().
+    pub fn read_lines(&mut self) -> Result<Vec<String>, AsciiError<W>> {
+        let mut lines = Vec::new();
+
+        while let Some(output) = self.next_output()? {
+            match output {
+                AsciiOutput::Line(line) => lines.push(line),
+                AsciiOutput::Value(word) => {
+                    self.pending.push_front(AsciiOutput::Value(word));
+                    return Err(AsciiError::NotAscii(word));
+                }
+            }
+        }
+
+        Ok(lines)
+    }
+
+    fn take_line(&mut self) -> AsciiOutput<W> {
+        AsciiOutput::Line(mem::take(&mut self.line))
+    }
+}
+
+#[test]
+fn lines_and_raw_values() {
+    use crate::intcode::assembler::assemble;
+
+    //echoes a line of input, then outputs `H`, 1000 and -1 on one line, and an unfinished `!`
+    let mem = assemble(
+        "
+        loop:   in c
+                out [c]
+                eq [c], 10, eol
+                jf [eol], @loop
+                out 72
+                out 1000
+                out -1
+                out 33
+                halt
+        c:      data 0
+        eol:    data 0
+        ",
+    )
+    .unwrap();
+
+    let mut ascii = AsciiIntcode::new(IntcodeRunner::new(&mem));
+    assert_eq!(ascii.next_output(), Ok(None));
+    assert_eq!(ascii.stopped(), Some(Ok(RunState::NeedsInput)));
+
+    ascii.send_line("hi");
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Line("hi".to_string()))));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Line("H".to_string()))));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Value(1000))));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Value(-1))));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Line("!".to_string()))));
+    assert_eq!(ascii.next_output(), Ok(None));
+    assert_eq!(ascii.stopped(), Some(Ok(RunState::Halted)));
+}
+
+#[test]
+fn partial_line_before_fault() {
+    //outputs `hi`, then faults on opcode 55
+    let mut ascii = AsciiIntcode::new(IntcodeRunner::new(&[104, 104, 104, 105, 55]));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Line("hi".to_string()))));
+    assert!(matches!(ascii.next_output(), Err(IntcodeError::UnsupportedOpcode(_))));
+    assert!(matches!(ascii.stopped(), Some(Err(IntcodeError::UnsupportedOpcode(_)))));
+
+    let mut ascii = AsciiIntcode::new(IntcodeRunner::new(&[104, 104, 104, 105, 55]));
+    assert!(matches!(ascii.read_lines(), Err(AsciiError::Fault(IntcodeError::UnsupportedOpcode(_)))));
+}
+
+#[test]
+fn read_lines_stops_at_values() {
+    //outputs `a`, 1000, then `b`
+    let mut ascii = AsciiIntcode::new(IntcodeRunner::new(&[104, 97, 104, 10, 104, 1000, 104, 98, 99]));
+    assert_eq!(ascii.read_lines(), Err(AsciiError::NotAscii(1000)));
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Value(1000))));
+    assert_eq!(ascii.read_lines(), Ok(vec!["b".to_string()]));
+}
+
+#[test]
+fn resumes_after_limit() {
+    let mut runner = IntcodeRunner::new(&[104, 97, 104, 98, 99]);
+    runner.set_step_budget(Some(1));
+    let mut ascii = AsciiIntcode::new(runner);
+    assert_eq!(ascii.next_output(), Ok(Some(AsciiOutput::Line("a".to_string()))));
+    assert!(matches!(ascii.stopped(), Some(Ok(RunState::LimitReached(_)))));
+
+    //stays stopped until resumed
+    ascii.runner_mut().set_step_budget(None);
+    assert_eq!(ascii.next_output(), Ok(None));
+    ascii.resume();
+    assert_eq!(ascii.read_lines(), Ok(vec!["b".to_string()]));
+    assert_eq!(ascii.stopped(), Some(Ok(RunState::Halted)));
+}
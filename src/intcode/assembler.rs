@@ -0,0 +1,401 @@
+//! assembles human-readable Intcode into the memory image that IntcodeRunner::new consumes.
+//!
+//! every line holds an optional label, followed by an optional statement and an optional comment:
+//!
+//! ```text
+//! ; echoes its input, doubled
+//! start:  in x
+//!         mul [x], 2, [rb+1]
+//!         out [rb+1]
+//!         jt 1, @start
+//! x:      data 0
+//! ```
+//!
+//! a statement is either an instruction or a `data` directive.
+//! instructions are one of `add`, `mul`, `in`, `out`, `jt`, `jf`, `lt`, `eq`, `arb` (adjust the
+//! relative base), `halt` and `reboot` (opcode 0, see extensions.rs), followed by their
+//! comma-separated operands:
+//!
+//! * `5` is an immediate value.
+//! * `@label` is the address of a label, as an immediate value.
+//! * `[10]`, `[label]` and plain `label` are position mode parameters.
+//! * `[rb+1]`, `[rb-1]` and `[rb]` are relative mode parameters.
+//!
+//! labels may be offset, as in `@label+2` or `[label-1]`.
+//! `data` places its comma-separated values (numbers or `@label` addresses) into memory as-is.
+
+use crate::intcode::{extensions, Opcode, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// every error holds the (1-based) line it was found on, followed by the offending text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsmError {
+    UnknownMnemonic(usize, String),
+    /// also holds the expected and actual operand count.
+    WrongOperandCount(usize, String, usize, usize),
+    BadOperand(usize, String),
+    BadLabel(usize, String),
+    DuplicateLabel(usize, String),
+    UnknownLabel(usize, String),
+    /// the operand an instruction writes to can't be in immediate mode.
+    ImmediateWrite(usize, String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic(line, mnemonic) => {
+                write!(f, "line {}: unknown mnemonic `{}`", line, mnemonic)
+            }
+            AsmError::WrongOperandCount(line, mnemonic, expected, found) => write!(
+                f,
+                "line {}: `{}` takes {} operands, found {}",
+                line, mnemonic, expected, found
+            ),
+            AsmError::BadOperand(line, operand) => {
+                write!(f, "line {}: malformed operand `{}`", line, operand)
+            }
+            AsmError::BadLabel(line, label) => write!(f, "line {}: malformed label `{}`", line, label),
+            AsmError::DuplicateLabel(line, label) => {
+                write!(f, "line {}: label `{}` is already defined", line, label)
+            }
+            AsmError::UnknownLabel(line, label) => write!(f, "line {}: unknown label `{}`", line, label),
+            AsmError::ImmediateWrite(line, operand) => write!(
+                f,
+                "line {}: can't write to immediate mode operand `{}`",
+                line, operand
+            ),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// a number, or a label's address plus some offset.
+enum Expr {
+    Num(isize),
+    Label(String, isize),
+}
+
+enum Operand {
+    Position(Expr),
+    Immediate(Expr),
+    Relative(isize),
+}
+
+enum StatementKind {
+    Instruction(String, Vec<(String, Operand)>),
+    Data(Vec<Expr>),
+}
+
+struct Statement {
+    line: usize,
+    kind: StatementKind,
+}
+
+fn arity(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "add" | "mul" | "lt" | "eq" => Some(3),
+        "jt" | "jf" => Some(2),
+        "in" | "out" | "arb" => Some(1),
+        "halt" | "reboot" => Some(0),
+        _ => None,
+    }
+}
+
+/// the index of the operand the instruction writes to, if any.
+fn written_operand(mnemonic: &str) -> Option<usize> {
+    match mnemonic {
+        "add" | "mul" | "lt" | "eq" => Some(2),
+        "in" => Some(0),
+        _ => None,
+    }
+}
+
+fn build_opcode(mnemonic: &str, vals: Vec<Value>) -> Opcode {
+    let mut vals = vals.into_iter();
+    let mut next = || vals.next().expect("operand count was checked when parsing");
+
+    match mnemonic {
+        "add" => Opcode::Add(next(), next(), next()),
+        "mul" => Opcode::Mul(next(), next(), next()),
+        "in" => Opcode::In(next()),
+        "out" => Opcode::Out(next()),
+        "jt" => Opcode::JumpIfTrue(next(), next()),
+        "jf" => Opcode::JumpIfFalse(next(), next()),
+        "lt" => Opcode::LT(next(), next(), next()),
+        "eq" => Opcode::EQ(next(), next(), next()),
+        "arb" => Opcode::BaseOffset(next()),
+        "halt" => Opcode::Halt,
+        "reboot" => extensions::reboot().opcode(Vec::new()),
+        _ => unreachable!("mnemonic was checked when parsing"),
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    let starts_well = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+
+    starts_well && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && s != "rb"
+}
+
+fn parse_num(s: &str) -> Option<isize> {
+    s.trim().parse().ok()
+}
+
+/// parses the `+ 2` or `-2` after a label or `rb`, which may have spaces after the sign.
+fn parse_addend(s: &str) -> Option<isize> {
+    let s = s.trim();
+    let (negative, digits) = match s.chars().next()? {
+        '+' => (false, s[1..].trim_start()),
+        '-' => (true, s[1..].trim_start()),
+        _ => return None,
+    };
+
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let num = parse_num(digits)?;
+    Some(if negative { -num } else { num })
+}
+
+/// parses `5`, `-5`, `label`, `label+2` and `label - 2`.
+fn parse_expr(s: &str) -> Option<Expr> {
+    let s = s.trim();
+
+    if let Some(num) = parse_num(s) {
+        return Some(Expr::Num(num));
+    }
+
+    let (label, addend) = match s.find(&['+', '-'][..]) {
+        Some(sign_idx) => (s[..sign_idx].trim(), parse_addend(&s[sign_idx..])?),
+        None => (s, 0),
+    };
+
+    if is_label(label) {
+        Some(Expr::Label(label.to_string(), addend))
+    } else {
+        None
+    }
+}
+
+fn parse_operand(s: &str) -> Option<Operand> {
+    if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+
+        if let Some(rel) = inner.strip_prefix("rb") {
+            let rel = rel.trim();
+            return match rel {
+                "" => Some(Operand::Relative(0)),
+                _ if rel.starts_with('+') || rel.starts_with('-') => parse_addend(rel).map(Operand::Relative),
+                _ => parse_expr(inner).map(Operand::Position), //just a label that starts with "rb"
+            };
+        }
+
+        return parse_expr(inner).map(Operand::Position);
+    }
+
+    if let Some(label) = s.strip_prefix('@') {
+        return match parse_expr(label)? {
+            expr @ Expr::Label(_, _) => Some(Operand::Immediate(expr)),
+            Expr::Num(_) => None,
+        };
+    }
+
+    match parse_expr(s)? {
+        expr @ Expr::Num(_) => Some(Operand::Immediate(expr)),
+        expr @ Expr::Label(_, _) => Some(Operand::Position(expr)),
+    }
+}
+
+fn split_operands(s: &str) -> Vec<String> {
+    if s.trim().is_empty() {
+        return Vec::new();
+    }
+
+    s.split(',').map(|operand| operand.trim().to_string()).collect()
+}
+
+/// parses a single statement, without its label.
+fn parse_statement(line: usize, s: &str) -> Result<StatementKind, AsmError> {
+    let (mnemonic, rest) = match s.find(char::is_whitespace) {
+        Some(idx) => (&s[..idx], &s[idx..]),
+        None => (s, ""),
+    };
+
+    let operands = split_operands(rest);
+
+    if mnemonic == "data" {
+        let values = operands
+            .into_iter()
+            .map(|operand| {
+                let value = match operand.strip_prefix('@') {
+                    Some(label) => parse_expr(label),
+                    None => parse_num(&operand).map(Expr::Num),
+                };
+
+                value.ok_or(AsmError::BadOperand(line, operand))
+            })
+            .collect::<Result<_, _>>()?;
+
+        return Ok(StatementKind::Data(values));
+    }
+
+    let expected = arity(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic(line, mnemonic.to_string()))?;
+    if operands.len() != expected {
+        return Err(AsmError::WrongOperandCount(line, mnemonic.to_string(), expected, operands.len()));
+    }
+
+    let operands = operands
+        .into_iter()
+        .map(|operand| match parse_operand(&operand) {
+            Some(parsed) => Ok((operand, parsed)),
+            None => Err(AsmError::BadOperand(line, operand)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(idx) = written_operand(mnemonic) {
+        if let (operand, Operand::Immediate(_)) = &operands[idx] {
+            return Err(AsmError::ImmediateWrite(line, operand.clone()));
+        }
+    }
+
+    Ok(StatementKind::Instruction(mnemonic.to_string(), operands))
+}
+
+fn resolve(line: usize, expr: &Expr, labels: &HashMap<String, isize>) -> Result<isize, AsmError> {
+    match expr {
+        Expr::Num(num) => Ok(*num),
+        Expr::Label(label, addend) => labels
+            .get(label)
+            .map(|addr| addr + addend)
+            .ok_or_else(|| AsmError::UnknownLabel(line, label.clone())),
+    }
+}
+
+pub fn assemble(src: &str) -> Result<Vec<isize>, AsmError> {
+    let mut labels: HashMap<String, isize> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+
+    //first pass: parse everything and find out where the labels point
+    for (idx, line_str) in src.lines().enumerate() {
+        let line = idx + 1;
+        let mut rest = line_str.split(';').next().unwrap_or_default().trim();
+
+        while let Some(colon_idx) = rest.find(':') {
+            let label = rest[..colon_idx].trim();
+            if !is_label(label) {
+                return Err(AsmError::BadLabel(line, label.to_string()));
+            }
+
+            if labels.insert(label.to_string(), addr).is_some() {
+                return Err(AsmError::DuplicateLabel(line, label.to_string()));
+            }
+
+            rest = rest[colon_idx + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let kind = parse_statement(line, rest)?;
+        addr += match &kind {
+            StatementKind::Instruction(_, operands) => operands.len() + 1,
+            StatementKind::Data(values) => values.len(),
+        } as isize;
+
+        statements.push(Statement { line, kind });
+    }
+
+    //second pass: now that all labels are known, emit the memory image
+    let mut mem = Vec::with_capacity(addr as usize);
+
+    for Statement { line, kind } in statements {
+        match kind {
+            StatementKind::Instruction(mnemonic, operands) => {
+                let vals = operands
+                    .iter()
+                    .map(|(_, operand)| match operand {
+                        Operand::Position(expr) => resolve(line, expr, &labels).map(Value::Position),
+                        Operand::Immediate(expr) => resolve(line, expr, &labels).map(Value::Immediate),
+                        Operand::Relative(rel) => Ok(Value::Relative(*rel)),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                mem.extend(build_opcode(&mnemonic, vals).encode());
+            }
+
+            StatementKind::Data(values) => {
+                for expr in values {
+                    mem.push(resolve(line, &expr, &labels)?);
+                }
+            }
+        }
+    }
+
+    Ok(mem)
+}
+
+#[test]
+fn assembles_all_parameter_modes() {
+    let src = "
+        in x        ; read into x
+        mul [x], 2, [rb+1]
+        out [rb + 1]
+        halt
+    x:  data 0, @x
+    ";
+
+    let expected = vec![3, 9, 21002, 9, 2, 1, 204, 1, 99, 0, 9];
+    assert_eq!(assemble(src), Ok(expected));
+}
+
+#[test]
+fn assembled_loop_runs() {
+    use crate::intcode::IntcodeRunner;
+
+    //counts down from its input to 1
+    let src = "
+    start:  in [n]
+    loop:   out [n]
+            add [n], -1, [n]
+            jt [n], @loop
+            halt
+    n:      data 0
+    ";
+
+    let mem = assemble(src).unwrap();
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.push_input(3);
+
+    let outputs: Vec<isize> = runner.outputs().collect::<Result<_, _>>().unwrap();
+    assert_eq!(outputs, vec![3, 2, 1]);
+}
+
+#[test]
+fn reports_line_of_error() {
+    assert_eq!(
+        assemble("in [x]\n\nout [y]\nx: data 0"),
+        Err(AsmError::UnknownLabel(3, "y".to_string()))
+    );
+    assert_eq!(
+        assemble("add 1, 2, 3"),
+        Err(AsmError::ImmediateWrite(1, "3".to_string()))
+    );
+}
+
+#[test]
+fn rejects_numbers_split_by_spaces() {
+    assert_eq!(assemble("out 1 2"), Err(AsmError::BadOperand(1, "1 2".to_string())));
+    assert_eq!(assemble("data 1 2"), Err(AsmError::BadOperand(1, "1 2".to_string())));
+    assert_eq!(assemble("out [rb + 1 2]"), Err(AsmError::BadOperand(1, "[rb + 1 2]".to_string())));
+
+    //spaces around the sign of an offset are fine
+    assert_eq!(assemble("x: out [x + 2]\nout [rb - 1]"), Ok(vec![4, 2, 204, -1]));
+}
@@ -0,0 +1,226 @@
+//! compiles Intcode programs ahead of time into trees of closures.
+//!
+//! every instruction of a Program is turned into a closure that executes it directly: parameter
+//! modes are resolved at compile time, so running an instruction doesn't decode anything or match
+//! on opcodes and modes. runners spawned from a CompiledProgram run the compiled closures
+//! wherever they can, and fall back to the interpreter for
+//!
+//! * instructions the program wrote over (self-modifying code), tracked as described in
+//!   program.rs,
+//! * addresses that weren't decoded as instructions, or whose operands can't be compiled,
+//! * everything, while watchpoints are set or a trace is being recorded, since compiled
+//!   instructions skip both.
+//!
+//! either way, the program behaves exactly as it would in the interpreter.
+
+use crate::intcode::program::Program;
+use crate::intcode::{IntcodeError, IntcodeRunner, Opcode, RunState, Value, Word};
+use std::sync::Arc;
+
+type StepResult<W> = Result<Option<RunState<W>>, IntcodeError<W>>;
+
+pub(super) type CompiledOp<W> = Box<dyn Fn(&mut IntcodeRunner<W>) -> StepResult<W> + Send + Sync>;
+
+type Load<W> = Box<dyn Fn(&IntcodeRunner<W>) -> Result<W, IntcodeError<W>> + Send + Sync>;
+
+/// resolves the address a parameter writes to.
+type Dest<W> = Box<dyn Fn(&IntcodeRunner<W>) -> Result<isize, IntcodeError<W>> + Send + Sync>;
+
+fn load<W: Word>(val: &Value<W>) -> Option<Load<W>> {
+    match *val {
+        Value::Position(addr) => {
+            let addr = addr.to_isize().filter(|&addr| addr >= 0)? as usize;
+            Some(Box::new(move |runner| Ok(runner.read(addr))))
+        }
+        Value::Immediate(imm) => Some(Box::new(move |_| Ok(imm))),
+        Value::Relative(rel_addr) => Some(Box::new(move |runner| {
+            let addr = runner.relative_addr(rel_addr)?;
+            runner.fetch(addr)
+        })),
+    }
+}
+
+fn dest<W: Word>(val: &Value<W>) -> Option<Dest<W>> {
+    match *val {
+        //like the interpreter, immediate mode writes go to the address given
+        Value::Position(addr) | Value::Immediate(addr) => {
+            let addr = addr.to_isize().filter(|&addr| addr >= 0)?;
+            Some(Box::new(move |_| Ok(addr)))
+        }
+        Value::Relative(rel_addr) => Some(Box::new(move |runner| runner.relative_addr(rel_addr))),
+    }
+}
+
+/// compiles an instruction located at `addr`, or returns None if the interpreter should run it.
+fn compile<W: Word>(addr: isize, opcode: &Opcode<W>) -> Option<CompiledOp<W>> {
+    let next = addr + opcode.params().len() as isize + 1;
+    let zero = W::default();
+    let one = W::from_isize(1);
+
+    let op: CompiledOp<W> = match opcode {
+        Opcode::Add(val1, val2, val3) => {
+            let (load1, load2, dest3) = (load(val1)?, load(val2)?, dest(val3)?);
+            Box::new(move |runner| {
+                let (op1, op2, addr) = (load1(runner)?, load2(runner)?, dest3(runner)?);
+                let sum = op1.sum(op2).ok_or_else(|| IntcodeError::Overflow(runner.fault()))?;
+                runner.set_mem(addr, sum)?;
+                runner.inst_ptr = next;
+                Ok(None)
+            })
+        }
+
+        Opcode::Mul(val1, val2, val3) => {
+            let (load1, load2, dest3) = (load(val1)?, load(val2)?, dest(val3)?);
+            Box::new(move |runner| {
+                let (op1, op2, addr) = (load1(runner)?, load2(runner)?, dest3(runner)?);
+                let product = op1.product(op2).ok_or_else(|| IntcodeError::Overflow(runner.fault()))?;
+                runner.set_mem(addr, product)?;
+                runner.inst_ptr = next;
+                Ok(None)
+            })
+        }
+
+        Opcode::In(val) => {
+            let dest1 = dest(val)?;
+            Box::new(move |runner| {
+                let input = runner.peek_next_input()?;
+                let addr = dest1(runner)?;
+                runner.set_mem(addr, input)?;
+                runner.consume_input();
+                runner.inst_ptr = next;
+                Ok(None)
+            })
+        }
+
+        Opcode::Out(val) => {
+            let load1 = load(val)?;
+            Box::new(move |runner| {
+                let output = load1(runner)?;
+                runner.produce_output(output);
+                runner.inst_ptr = next;
+                Ok(Some(RunState::Output(output)))
+            })
+        }
+
+        Opcode::JumpIfTrue(val1, val2) | Opcode::JumpIfFalse(val1, val2) => {
+            let jump_if = matches!(opcode, Opcode::JumpIfTrue(_, _));
+            let (load1, load2) = (load(val1)?, load(val2)?);
+            Box::new(move |runner| {
+                runner.inst_ptr = if (load1(runner)? != zero) == jump_if {
+                    let target = load2(runner)?;
+                    runner.to_addr(target)?
+                } else {
+                    next
+                };
+                Ok(None)
+            })
+        }
+
+        Opcode::LT(val1, val2, val3) | Opcode::EQ(val1, val2, val3) => {
+            let is_lt = matches!(opcode, Opcode::LT(_, _, _));
+            let (load1, load2, dest3) = (load(val1)?, load(val2)?, dest(val3)?);
+            Box::new(move |runner| {
+                let (op1, op2, addr) = (load1(runner)?, load2(runner)?, dest3(runner)?);
+                let res = if is_lt { op1 < op2 } else { op1 == op2 };
+                runner.set_mem(addr, if res { one } else { zero })?;
+                runner.inst_ptr = next;
+                Ok(None)
+            })
+        }
+
+        Opcode::BaseOffset(val) => {
+            let load1 = load(val)?;
+            Box::new(move |runner| {
+                let offset_change = load1(runner)?;
+                runner.offset = runner.relative_addr(offset_change)?;
+                runner.inst_ptr = next;
+                Ok(None)
+            })
+        }
+
+        Opcode::Halt => Box::new(move |runner| {
+            runner.halted = true;
+            runner.inst_ptr = next;
+            Ok(Some(RunState::Halted))
+        }),
+
+        //rare enough that it's not worth compiling
+        Opcode::Extension(_) => return None,
+    };
+
+    Some(op)
+}
+
+pub struct CompiledProgram<W = isize> {
+    program: Program<W>,
+    ops: Arc<Vec<Option<CompiledOp<W>>>>,
+}
+
+impl<W: Word> CompiledProgram<W> {
+    pub fn new(program: &Program<W>) -> CompiledProgram<W> {
+        let ops = program
+            .decoded
+            .iter()
+            .enumerate()
+            .map(|(addr, opcode)| opcode.as_ref().and_then(|opcode| compile(addr as isize, opcode)))
+            .collect();
+
+        CompiledProgram {
+            program: program.clone(),
+            ops: Arc::new(ops),
+        }
+    }
+
+    pub fn program(&self) -> &Program<W> {
+        &self.program
+    }
+
+    /// a fresh runner that runs the compiled code.
+    pub fn runner(&self) -> IntcodeRunner<W> {
+        let mut runner = self.program.runner();
+        runner.compiled = Some(Arc::clone(&self.ops));
+        runner
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// executes the current instruction if it's been compiled and nothing requires interpreting it.
+    pub(super) fn step_compiled(&mut self) -> Option<StepResult<W>> {
+        if self.trace.is_some() || !self.watchpoints.is_empty() || self.inst_ptr < 0 {
+            return None;
+        }
+
+        if self.is_code_dirty(self.inst_ptr as usize) {
+            return None;
+        }
+
+        //the ops are taken out of the runner while they run, since they need it mutably
+        let ops = self.compiled.take()?;
+        let res = match ops.get(self.inst_ptr as usize) {
+            Some(Some(op)) => Some(op(self)),
+            _ => None,
+        };
+
+        self.compiled = Some(ops);
+        res
+    }
+}
+
+#[test]
+fn matches_the_interpreter() {
+    use crate::intcode::fixtures::QUINE;
+
+    let compiled = CompiledProgram::new(&Program::new(QUINE));
+
+    let outputs: Vec<isize> = compiled.runner().outputs().collect::<Result<_, _>>().unwrap();
+    assert_eq!(outputs, QUINE);
+}
+
+#[test]
+fn interprets_self_modified_code() {
+    let compiled = CompiledProgram::new(&Program::new(crate::intcode::fixtures::OVERWRITES_OUT));
+
+    let mut runner = compiled.runner();
+    assert_eq!(runner.run(), Ok(RunState::Halted));
+    assert_eq!(runner.inst_ptr(), 5);
+}
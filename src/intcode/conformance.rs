@@ -0,0 +1,140 @@
+//! the example programs published with the puzzles, run on every way of running a program: a plain
+//! runner, one spawned from a Program, and one spawned from a CompiledProgram.
+//!
+//! each table row is a program, its inputs, in the order they're consumed, and either the outputs
+//! it should produce or the memory it should end up with.
+
+use crate::intcode::fixtures::QUINE;
+use crate::intcode::{CompiledProgram, IntcodeRunner, Program, RunState};
+use std::collections::VecDeque;
+
+/// (program, inputs, outputs)
+type IoCase = (&'static [isize], &'static [isize], &'static [isize]);
+
+/// (program, memory once it halted)
+type MemCase = (&'static [isize], &'static [isize]);
+
+fn runners(mem: &[isize]) -> Vec<(&'static str, IntcodeRunner)> {
+    let program = Program::new(mem);
+
+    vec![
+        ("interpreted", IntcodeRunner::new(mem)),
+        ("pre-decoded", program.runner()),
+        ("compiled", CompiledProgram::new(&program).runner()),
+    ]
+}
+
+fn check_io(cases: &[IoCase]) {
+    for &(mem, inputs, outputs) in cases {
+        for (how, mut runner) in runners(mem) {
+            let mut source: VecDeque<isize> = inputs.iter().copied().collect();
+            let mut found = Vec::new();
+
+            let res = runner.run_with(&mut source, &mut found);
+            assert_eq!(res, Ok(RunState::Halted), "{:?} {} with inputs {:?}", mem, how, inputs);
+            assert_eq!(found, outputs, "{:?} {} with inputs {:?}", mem, how, inputs);
+        }
+    }
+}
+
+fn check_mem(cases: &[MemCase]) {
+    for &(mem, expected) in cases {
+        for (how, mut runner) in runners(mem) {
+            assert_eq!(runner.run(), Ok(RunState::Halted), "{:?} {}", mem, how);
+
+            let found: Vec<isize> = (0..expected.len()).map(|addr| runner.read(addr)).collect();
+            assert_eq!(found, expected, "{:?} {}", mem, how);
+        }
+    }
+}
+
+#[test]
+fn day2_arithmetic() {
+    check_mem(&[
+        (&[1, 0, 0, 0, 99], &[2, 0, 0, 0, 99]),
+        (&[2, 3, 0, 3, 99], &[2, 3, 0, 6, 99]),
+        (&[2, 4, 4, 5, 99, 0], &[2, 4, 4, 5, 99, 9801]),
+        (&[1, 1, 1, 4, 99, 5, 6, 0, 99], &[30, 1, 1, 4, 2, 5, 6, 0, 99]),
+        (
+            &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            &[3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50],
+        ),
+    ]);
+}
+
+#[test]
+fn day5_modes() {
+    check_io(&[(&[3, 0, 4, 0, 99], &[42], &[42])]);
+
+    check_mem(&[
+        (&[1002, 4, 3, 4, 33], &[1002, 4, 3, 4, 99]),
+        (&[1101, 100, -1, 4, 0], &[1101, 100, -1, 4, 99]),
+    ]);
+}
+
+#[test]
+fn day5_compare_and_jump() {
+    //equal to 8, position mode
+    const EQ_POS: &[isize] = &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    //less than 8, position mode
+    const LT_POS: &[isize] = &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+    //equal to 8, immediate mode
+    const EQ_IMM: &[isize] = &[3, 3, 1108, -1, 8, 3, 4, 3, 99];
+    //less than 8, immediate mode
+    const LT_IMM: &[isize] = &[3, 3, 1107, -1, 8, 3, 4, 3, 99];
+    //0 if the input is 0, else 1
+    const JUMP_POS: &[isize] = &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+    const JUMP_IMM: &[isize] = &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+    //999 below 8, 1000 for 8, 1001 above 8
+    const CMP_8: &[isize] = &[
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+
+    check_io(&[
+        (EQ_POS, &[8], &[1]),
+        (EQ_POS, &[7], &[0]),
+        (LT_POS, &[7], &[1]),
+        (LT_POS, &[8], &[0]),
+        (EQ_IMM, &[8], &[1]),
+        (EQ_IMM, &[9], &[0]),
+        (LT_IMM, &[-3], &[1]),
+        (LT_IMM, &[9], &[0]),
+        (JUMP_POS, &[0], &[0]),
+        (JUMP_POS, &[5], &[1]),
+        (JUMP_IMM, &[0], &[0]),
+        (JUMP_IMM, &[-5], &[1]),
+        (CMP_8, &[7], &[999]),
+        (CMP_8, &[8], &[1000]),
+        (CMP_8, &[9], &[1001]),
+    ]);
+}
+
+#[test]
+fn day9_large_numbers_and_quine() {
+    check_io(&[
+        (QUINE, &[], QUINE),
+        (&[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[], &[1219070632396864]),
+        (&[104, 1125899906842624, 99], &[], &[1125899906842624]),
+    ]);
+}
+
+#[test]
+fn relative_mode() {
+    check_io(&[
+        //output in every mode, with a negative relative base
+        (&[109, -1, 4, 1, 99], &[], &[-1]),
+        (&[109, -1, 104, 1, 99], &[], &[1]),
+        (&[109, -1, 204, 1, 99], &[], &[109]),
+        //adjusting the relative base in every mode
+        (&[109, 1, 9, 2, 204, -6, 99], &[], &[204]),
+        (&[109, 1, 109, 9, 204, -6, 99], &[], &[204]),
+        (&[109, 1, 209, -1, 204, -106, 99], &[], &[204]),
+        //writing input in position and relative mode
+        (&[109, 1, 3, 3, 204, 2, 99], &[17], &[17]),
+        (&[109, 1, 203, 2, 204, 2, 99], &[23], &[23]),
+        //writing past the end of the program, relative to the relative base
+        (&[109, 10, 21101, 4, 5, 0, 204, 0, 99], &[], &[9]),
+    ]);
+}
@@ -0,0 +1,380 @@
+//! an interactive debugger for IntcodeRunner.
+//!
+//! the commands are listed in HELP, which `help` prints. addresses and values are decimal.
+
+use crate::intcode::{IntcodeRunner, RunState, WatchAction, Watchpoint};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+/// how many instructions the debugger can undo.
+const JOURNAL_LIMIT: usize = 100_000;
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+c, continue          run until a breakpoint, halt, fault or missing input
+back [n]             undo the last n instructions (default 1)
+lastwrite <addr>     undo instructions until the one that last wrote addr is next
+b, break [addr]      set a breakpoint, or list them when no address is given
+d, delete <addr>     remove a breakpoint
+m, mem <addr> [n]    show n memory words (default 8) starting at addr
+set <addr> <value>   write a value into memory, unseen by watchpoints and not undoable
+i, input <v>...      queue inputs, which are consumed in the given order
+r, regs              show the instruction pointer, relative base and pending inputs
+l, list [addr] [n]   disassemble n instructions (default 5) starting at addr (default: current)
+watch <addr> [n]     pause after n words (default 1) starting at addr are written
+rwatch <addr> [n]    pause after they're read
+awatch <addr> [n]    pause after they're read or written
+unwatch <addr>       remove every watchpoint covering addr
+h, help              show this text
+q, quit              leave the debugger
+";
+
+/// why executing stopped, as far as the debugger is concerned.
+enum Stop {
+    Breakpoint,
+    Watchpoint,
+    Limit,
+    Halted,
+    NeedsInput,
+    Fault,
+}
+
+pub struct Debugger {
+    runner: IntcodeRunner,
+    breakpoints: BTreeSet<isize>,
+}
+
+impl Debugger {
+    pub fn new(mut runner: IntcodeRunner) -> Debugger {
+        runner.start_journal(JOURNAL_LIMIT);
+
+        Debugger {
+            runner,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn runner(&self) -> &IntcodeRunner {
+        &self.runner
+    }
+
+    pub fn into_runner(self) -> IntcodeRunner {
+        self.runner
+    }
+
+    pub fn add_breakpoint(&mut self, addr: isize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: isize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// reads commands from stdin until told to quit.
+    pub fn run_interactive(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        self.repl(stdin.lock(), io::stdout())
+    }
+
+    pub fn repl(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        write!(out, "(intcode) ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            if !self.execute(&line?, &mut out)? {
+                break;
+            }
+
+            write!(out, "(intcode) ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// executes a single command. returns false iff the command was to quit.
+    pub fn execute(&mut self, command: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(true),
+        };
+
+        let args: Result<Vec<isize>, _> = words.map(|word| word.parse()).collect();
+        let args = match args {
+            Ok(args) => args,
+            Err(_) => {
+                writeln!(out, "arguments must be numbers")?;
+                return Ok(true);
+            }
+        };
+
+        match (name, &args[..]) {
+            ("s", []) | ("step", []) => self.step(1, out)?,
+            ("s", [n]) | ("step", [n]) => self.step(*n, out)?,
+            ("c", []) | ("continue", []) => self.cont(out)?,
+            ("back", []) => self.back(1, out)?,
+            ("back", [n]) => self.back(*n, out)?,
+            ("lastwrite", [addr]) => self.back_to_write(*addr, out)?,
+            ("b", []) | ("break", []) => {
+                let breakpoints: Vec<String> = self.breakpoints.iter().map(|addr| addr.to_string()).collect();
+                writeln!(out, "breakpoints: {}", breakpoints.join(", "))?;
+            }
+            ("b", [addr]) | ("break", [addr]) => self.add_breakpoint(*addr),
+            ("d", [addr]) | ("delete", [addr]) => {
+                if !self.remove_breakpoint(*addr) {
+                    writeln!(out, "no breakpoint at {}", addr)?;
+                }
+            }
+            ("m", [addr]) | ("mem", [addr]) => self.show_mem(*addr, 8, out)?,
+            ("m", [addr, n]) | ("mem", [addr, n]) => self.show_mem(*addr, *n, out)?,
+            //written directly, so that watchpoints and the trace don't see it as the program's
+            ("set", [addr, _]) if *addr < 0 => writeln!(out, "can't set negative addresses")?,
+            ("set", [addr, val]) => self.runner.write(*addr as usize, *val),
+            ("i", inputs) | ("input", inputs) if !inputs.is_empty() => {
+                //inputs are consumed from the back, so the newest input goes in front
+                for &input in inputs {
+                    self.runner.push_input_front(input);
+                }
+            }
+            ("r", []) | ("regs", []) => self.show_regs(out)?,
+            ("l", []) | ("list", []) => self.list(self.runner.inst_ptr, 5, out)?,
+            ("l", [addr]) | ("list", [addr]) => self.list(*addr, 5, out)?,
+            ("l", [addr, n]) | ("list", [addr, n]) => self.list(*addr, *n, out)?,
+            ("watch", [addr]) => self.watch(Watchpoint::writes, *addr, 1, out)?,
+            ("watch", [addr, n]) => self.watch(Watchpoint::writes, *addr, *n, out)?,
+            ("rwatch", [addr]) => self.watch(Watchpoint::reads, *addr, 1, out)?,
+            ("rwatch", [addr, n]) => self.watch(Watchpoint::reads, *addr, *n, out)?,
+            ("awatch", [addr]) => self.watch(Watchpoint::accesses, *addr, 1, out)?,
+            ("awatch", [addr, n]) => self.watch(Watchpoint::accesses, *addr, *n, out)?,
+            ("unwatch", [addr]) => {
+                if !self.runner.remove_watchpoints_at(*addr) {
+                    writeln!(out, "no watchpoint at {}", addr)?;
+                }
+            }
+            ("h", []) | ("help", []) => write!(out, "{}", HELP)?,
+            ("q", []) | ("quit", []) => return Ok(false),
+            _ => writeln!(out, "unknown command, type `help` for a list of commands")?,
+        };
+
+        Ok(true)
+    }
+
+    /// executes the current instruction, unless the program can't go on.
+    fn step_once(&mut self, out: &mut impl Write) -> io::Result<Option<Stop>> {
+        if self.runner.has_halted() {
+            writeln!(out, "the program has halted")?;
+            return Ok(Some(Stop::Halted));
+        }
+
+        match self.runner.step() {
+            Ok(Some(RunState::Output(output))) => {
+                writeln!(out, "output: {}", output)?;
+                Ok(None)
+            }
+            Ok(Some(RunState::NeedsInput)) => {
+                writeln!(out, "waiting for input")?;
+                Ok(Some(Stop::NeedsInput))
+            }
+            Ok(Some(RunState::Halted)) => {
+                writeln!(out, "halted")?;
+                Ok(Some(Stop::Halted))
+            }
+            Ok(Some(RunState::Watchpoint(hit))) => {
+                writeln!(out, "watchpoint: {}", hit)?;
+                Ok(Some(Stop::Watchpoint))
+            }
+            Ok(Some(RunState::LimitReached(limit))) => {
+                writeln!(out, "stopped: {}", limit)?;
+                Ok(Some(Stop::Limit))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                writeln!(out, "fault: {}", e)?;
+                Ok(Some(Stop::Fault))
+            }
+        }
+    }
+
+    fn step(&mut self, n: isize, out: &mut impl Write) -> io::Result<()> {
+        for _ in 0..n {
+            if self.step_once(out)?.is_some() {
+                break;
+            }
+        }
+
+        self.list(self.runner.inst_ptr, 1, out)
+    }
+
+    fn cont(&mut self, out: &mut impl Write) -> io::Result<()> {
+        //always execute at least one instruction, so we don't get stuck on the current breakpoint
+        let mut stop = self.step_once(out)?;
+
+        while stop.is_none() {
+            if self.breakpoints.contains(&self.runner.inst_ptr) {
+                stop = Some(Stop::Breakpoint);
+                break;
+            }
+
+            stop = self.step_once(out)?;
+        }
+
+        if let Some(Stop::Breakpoint) = stop {
+            writeln!(out, "breakpoint at {}", self.runner.inst_ptr)?;
+        }
+
+        self.list(self.runner.inst_ptr, 1, out)
+    }
+
+    fn back(&mut self, n: isize, out: &mut impl Write) -> io::Result<()> {
+        let undone = self.runner.step_back(n.max(0) as usize);
+        if (undone as isize) < n {
+            writeln!(out, "only {} instructions could be undone", undone)?;
+        }
+
+        self.list(self.runner.inst_ptr, 1, out)
+    }
+
+    fn back_to_write(&mut self, addr: isize, out: &mut impl Write) -> io::Result<()> {
+        if addr < 0 || !self.runner.step_back_to_write(addr as usize) {
+            return writeln!(out, "no undoable write to {}", addr);
+        }
+
+        self.list(self.runner.inst_ptr, 1, out)
+    }
+
+    /// pauses on the accesses `watchpoint` watches for to the `n` words starting at `addr`.
+    fn watch(
+        &mut self,
+        watchpoint: fn(Range<isize>, WatchAction) -> Watchpoint,
+        addr: isize,
+        n: isize,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        match addr.checked_add(n) {
+            Some(end) => self.runner.add_watchpoint(watchpoint(addr..end, WatchAction::Pause)),
+            None => writeln!(out, "addresses out of range")?,
+        }
+
+        Ok(())
+    }
+
+    fn show_mem(&self, addr: isize, n: isize, out: &mut impl Write) -> io::Result<()> {
+        const WORDS_PER_LINE: isize = 8;
+
+        if addr < 0 {
+            return writeln!(out, "can't show negative addresses");
+        }
+
+        let end = match addr.checked_add(n) {
+            Some(end) => end,
+            None => return writeln!(out, "addresses out of range"),
+        };
+
+        for line_start in (addr..end).step_by(WORDS_PER_LINE as usize) {
+            let line_end = line_start.saturating_add(WORDS_PER_LINE).min(end);
+            let words: Vec<String> = (line_start..line_end)
+                .map(|a| self.runner.read(a as usize).to_string())
+                .collect();
+
+            writeln!(out, "{:>6}: {}", line_start, words.join(" "))?;
+        }
+
+        Ok(())
+    }
+
+    fn show_regs(&self, out: &mut impl Write) -> io::Result<()> {
+        //inputs are consumed from the back
+        let inputs: Vec<String> = self.runner.inputs.iter().rev().map(|input| input.to_string()).collect();
+
+        writeln!(out, "instruction pointer: {}", self.runner.inst_ptr)?;
+        writeln!(out, "relative base: {}", self.runner.offset)?;
+        writeln!(out, "pending inputs: [{}]", inputs.join(", "))?;
+        writeln!(out, "last output: {:?}", self.runner.output)?;
+        writeln!(out, "halted: {}", self.runner.halted)
+    }
+
+    fn list(&self, addr: isize, n: isize, out: &mut impl Write) -> io::Result<()> {
+        let mut addr = addr;
+
+        for _ in 0..n {
+            let marker = if addr == self.runner.inst_ptr { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&addr) { "*" } else { " " };
+
+            let len = match self.runner.decode_at(addr) {
+                Ok(opcode) => {
+                    writeln!(out, "{}{}{:>6}: {}", marker, breakpoint, addr, opcode)?;
+                    opcode.params().len() as isize + 1
+                }
+                Err(_) => {
+                    let word = if addr < 0 { 0 } else { self.runner.read(addr as usize) };
+                    writeln!(out, "{}{}{:>6}: data {}", marker, breakpoint, addr, word)?;
+                    1
+                }
+            };
+
+            addr = match addr.checked_add(len) {
+                Some(next) => next,
+                None => return writeln!(out, "addresses out of range"),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn stops_at_breakpoints() {
+    //outputs its input, twice
+    let mem = vec![3, 9, 4, 9, 4, 9, 99, 0, 0, 0];
+    let mut debugger = Debugger::new(IntcodeRunner::new(&mem));
+
+    let commands = "b 4\ni 42\nc\nm 9 1\nc\nq\n";
+    let mut out = Vec::new();
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("output: 42\nbreakpoint at 4\n=>*     4: out [9]"));
+    assert!(out.contains("     9: 42\n"));
+    assert!(out.contains("output: 42\nhalted\n"));
+}
+
+#[test]
+fn stops_at_watchpoints() {
+    let mem = vec![3, 9, 4, 9, 4, 9, 99, 0, 0, 0];
+    let mut debugger = Debugger::new(IntcodeRunner::new(&mem));
+
+    let commands = "watch 9\ni 7\nc\nq\n";
+    let mut out = Vec::new();
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains("watchpoint: write of 7 to [9] by the instruction at 0\n=>      2: out [9]"));
+}
+
+#[test]
+fn set_ignores_watchpoints() {
+    let mem = vec![3, 9, 4, 9, 4, 9, 99, 0, 0, 0];
+    let mut debugger = Debugger::new(IntcodeRunner::new(&mem));
+
+    let commands = "watch 9\nset 9 5\ni 7\ns\nm 9 1\nq\n";
+    let mut out = Vec::new();
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(!out.contains("write of 5"));
+    assert!(out.contains("watchpoint: write of 7 to [9] by the instruction at 0\n"));
+}
+
+#[test]
+fn rejects_addresses_out_of_range() {
+    let mut debugger = Debugger::new(IntcodeRunner::new(&[99]));
+
+    let commands = "watch 9223372036854775807\nm 9223372036854775807 2\nl 9223372036854775807\nq\n";
+    let mut out = Vec::new();
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert_eq!(out.matches("addresses out of range").count(), 3);
+}
@@ -0,0 +1,198 @@
+//! input sources and output sinks that can be attached to a runner with run_with().
+//!
+//! the runner's own input queue is still consumed first. a source is only asked for an input once
+//! the queue runs dry, and every input it provides is consumed in the order it's provided, so
+//! callers never have to push inputs in reverse.
+//!
+//! sources: VecDeque (in order), Repeat (like InputMode::SingleInput), Ascii text, Stdin, mpsc
+//! receivers, closures returning `Option<W>`, and other runners, which run until their next output.
+//! sinks: Vec, Stdout, mpsc senders and closures taking a word.
+
+use crate::intcode::{IntcodeError, IntcodeRunner, RunState, Word};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::sync::mpsc::{Receiver, Sender};
+
+pub trait InputSource<W> {
+    /// the next input, or None if there's none available, in which case the runner stops with
+    /// RunState::NeedsInput.
+    fn next_input(&mut self) -> Option<W>;
+}
+
+pub trait OutputSink<W> {
+    fn send(&mut self, output: W);
+}
+
+impl<W, F: FnMut() -> Option<W>> InputSource<W> for F {
+    fn next_input(&mut self) -> Option<W> {
+        self()
+    }
+}
+
+impl<W> InputSource<W> for VecDeque<W> {
+    fn next_input(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+/// provides the same input forever.
+pub struct Repeat<W>(pub W);
+
+impl<W: Copy> InputSource<W> for Repeat<W> {
+    fn next_input(&mut self) -> Option<W> {
+        Some(self.0)
+    }
+}
+
+/// provides the bytes of a text as inputs, one character per input.
+pub struct Ascii {
+    bytes: VecDeque<u8>,
+}
+
+impl Ascii {
+    pub fn new(text: &str) -> Ascii {
+        Ascii {
+            bytes: text.bytes().collect(),
+        }
+    }
+
+    /// appends more text to the inputs still to be provided.
+    pub fn push_str(&mut self, text: &str) {
+        self.bytes.extend(text.bytes());
+    }
+}
+
+impl<W: Word> InputSource<W> for Ascii {
+    fn next_input(&mut self) -> Option<W> {
+        self.bytes.pop_front().map(|byte| W::from_isize(byte as isize))
+    }
+}
+
+/// reads one word per line from stdin. runs dry at the end of stdin or on a line that isn't a word.
+pub struct Stdin;
+
+impl<W: Word> InputSource<W> for Stdin {
+    fn next_input(&mut self) -> Option<W> {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => line.trim().parse().ok(),
+        }
+    }
+}
+
+/// blocks until an input arrives, and runs dry once every sender is gone.
+impl<W> InputSource<W> for Receiver<W> {
+    fn next_input(&mut self) -> Option<W> {
+        self.recv().ok()
+    }
+}
+
+/// runs the other runner until its next output. it runs dry once that runner halts, needs input,
+/// hits a pausing watchpoint, reaches a limit or faults.
+impl<W: Word> InputSource<W> for IntcodeRunner<W> {
+    fn next_input(&mut self) -> Option<W> {
+        match self.run() {
+            Ok(RunState::Output(output)) => Some(output),
+            _ => None,
+        }
+    }
+}
+
+impl<W, F: FnMut(W)> OutputSink<W> for F {
+    fn send(&mut self, output: W) {
+        self(output)
+    }
+}
+
+impl<W> OutputSink<W> for Vec<W> {
+    fn send(&mut self, output: W) {
+        self.push(output)
+    }
+}
+
+impl<W> OutputSink<W> for VecDeque<W> {
+    fn send(&mut self, output: W) {
+        self.push_back(output)
+    }
+}
+
+/// prints every output on its own line.
+pub struct Stdout;
+
+impl<W: Word> OutputSink<W> for Stdout {
+    fn send(&mut self, output: W) {
+        println!("{}", output);
+    }
+}
+
+/// outputs sent after the receiver is gone are dropped.
+impl<W> OutputSink<W> for Sender<W> {
+    fn send(&mut self, output: W) {
+        let _ = Sender::send(self, output);
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// runs the program, sending every output to `output`, and taking inputs from `input` once
+    /// the runner's own inputs run out. returns once the program halts, hits a pausing watchpoint,
+    /// reaches a limit or needs an input `input` doesn't have. like run(), a runner that has
+    /// already halted runs nothing.
+    pub fn run_with<I, O>(&mut self, input: &mut I, output: &mut O) -> Result<RunState<W>, IntcodeError<W>>
+    where
+        I: InputSource<W> + ?Sized,
+        O: OutputSink<W> + ?Sized,
+    {
+        loop {
+            match self.run()? {
+                RunState::Output(val) => output.send(val),
+                RunState::NeedsInput => match input.next_input() {
+                    Some(val) => self.push_input(val),
+                    None => return Ok(RunState::NeedsInput),
+                },
+                run_state => return Ok(run_state),
+            }
+        }
+    }
+}
+
+#[test]
+fn chains_runners() {
+    //outputs 5
+    let mut first = IntcodeRunner::new(&[104, 5, 99]);
+
+    //adds its two inputs
+    let mut second = IntcodeRunner::new(&[3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99]);
+    second.push_input(100);
+
+    let mut outputs = Vec::new();
+    assert_eq!(second.run_with(&mut first, &mut outputs), Ok(RunState::Halted));
+    assert_eq!(outputs, vec![105]);
+}
+
+#[test]
+fn halted_runners_stay_dry() {
+    //halts, with an output after the Halt
+    let mut first = IntcodeRunner::new(&[99, 104, 5, 99]);
+    assert_eq!(first.run(), Ok(RunState::Halted));
+    assert_eq!(first.next_input(), None);
+
+    let mut outputs = Vec::new();
+    assert_eq!(first.run_with(&mut VecDeque::new(), &mut outputs), Ok(RunState::Halted));
+    assert!(outputs.is_empty());
+}
+
+#[test]
+fn feeds_text_in_order() {
+    //echoes every input until it reads a 0
+    let echo = vec![3, 100, 1006, 100, 10, 4, 100, 1105, 1, 0, 99];
+    let mut runner = IntcodeRunner::new(&echo);
+
+    let mut outputs = Vec::new();
+    let mut text = Ascii::new("hi\n");
+    assert_eq!(runner.run_with(&mut text, &mut outputs), Ok(RunState::NeedsInput));
+
+    text.push_str("!\0");
+    assert_eq!(runner.run_with(&mut text, &mut outputs), Ok(RunState::Halted));
+    assert_eq!(outputs, "hi\n!".bytes().map(|byte| byte as isize).collect::<Vec<_>>());
+}
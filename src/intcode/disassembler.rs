@@ -0,0 +1,260 @@
+//! renders a memory image as an annotated program listing.
+//!
+//! Intcode doesn't separate code from data, so the code is found heuristically: starting from
+//! address 0, every instruction that control can statically flow to is code. since programs
+//! call functions by pushing an immediate return address (`add @ret, 0, [rb]`) and jumping
+//! through the stack, immediate values moved around like that are also followed, as long as they
+//! decode into instructions. everything else is data.
+//!
+//! instructions are decoded with the InstructionSet given, so that extensions such as reboot() are
+//! recognised as code.
+
+use crate::intcode::extensions::REBOOT;
+use crate::intcode::{InstructionSet, IntcodeRunner, Opcode, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub enum Line {
+    Instruction(isize, Opcode),
+    /// consecutive words that aren't code, starting at the held address.
+    Data(isize, Vec<isize>),
+}
+
+impl Line {
+    pub fn addr(&self) -> isize {
+        match *self {
+            Line::Instruction(addr, _) | Line::Data(addr, _) => addr,
+        }
+    }
+}
+
+/// addresses that might be reached after executing `opcode`, when it's located at `addr`.
+/// jumps through memory can't be resolved statically, so they're left out.
+pub fn successors(addr: isize, opcode: &Opcode) -> Vec<isize> {
+    let next = addr + opcode.params().len() as isize + 1;
+
+    let jump_target = |target: &Value| match *target {
+        Value::Immediate(target) => Some(target),
+        _ => None,
+    };
+
+    match opcode {
+        Opcode::Halt => vec![],
+        Opcode::Extension(op) if op.code == REBOOT => vec![0],
+        Opcode::JumpIfTrue(cond, target) => {
+            let always_jumps = matches!(*cond, Value::Immediate(c) if c != 0);
+            let falls_through = !always_jumps;
+            jump_target(target).into_iter().chain(Some(next).filter(|_| falls_through)).collect()
+        }
+        Opcode::JumpIfFalse(cond, target) => {
+            let always_jumps = *cond == Value::Immediate(0);
+            let falls_through = !always_jumps;
+            jump_target(target).into_iter().chain(Some(next).filter(|_| falls_through)).collect()
+        }
+        _ => vec![next],
+    }
+}
+
+/// immediate values that are only being moved, e.g. a return address being pushed,
+/// and might therefore point to code.
+pub(super) fn code_pointers(opcode: &Opcode) -> Vec<isize> {
+    match opcode {
+        Opcode::Add(Value::Immediate(a), Value::Immediate(b), _) if *b == 0 => vec![*a],
+        Opcode::Add(Value::Immediate(a), Value::Immediate(b), _) if *a == 0 => vec![*b],
+        Opcode::Mul(Value::Immediate(a), Value::Immediate(b), _) if *b == 1 => vec![*a],
+        Opcode::Mul(Value::Immediate(a), Value::Immediate(b), _) if *a == 1 => vec![*b],
+        _ => vec![],
+    }
+}
+
+/// finds the instructions of the program, keyed by address.
+pub fn find_code(mem: &[isize], instructions: &InstructionSet) -> BTreeMap<isize, Opcode> {
+    let mem_len = mem.len() as isize;
+    let mut runner = IntcodeRunner::new(mem);
+    runner.set_instructions(instructions.clone());
+
+    let mut instructions = BTreeMap::new();
+    let mut is_code = vec![false; mem.len()];
+
+    let mut to_visit = vec![0];
+    let mut possible_pointers = Vec::new();
+
+    loop {
+        while let Some(addr) = to_visit.pop() {
+            if addr < 0 || addr >= mem_len || is_code[addr as usize] {
+                continue;
+            }
+
+            let opcode = match runner.decode_at(addr) {
+                Ok(opcode) => opcode,
+                Err(_) => continue,
+            };
+
+            let end = addr + opcode.params().len() as isize + 1;
+            if end > mem_len || (addr..end).any(|a| is_code[a as usize]) {
+                continue;
+            }
+
+            for a in addr..end {
+                is_code[a as usize] = true;
+            }
+
+            to_visit.extend(successors(addr, &opcode));
+            possible_pointers.extend(code_pointers(&opcode));
+            instructions.insert(addr, opcode);
+        }
+
+        //only guess once everything that's certainly code has been found
+        match possible_pointers.pop() {
+            Some(addr) => to_visit.push(addr),
+            None => break,
+        }
+    }
+
+    instructions
+}
+
+pub fn disassemble(mem: &[isize], instructions: &InstructionSet) -> Vec<Line> {
+    const DATA_PER_LINE: usize = 8;
+
+    let mut instructions = find_code(mem, instructions);
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while (addr as usize) < mem.len() {
+        if let Some(opcode) = instructions.remove(&addr) {
+            let len = opcode.params().len() as isize + 1;
+            lines.push(Line::Instruction(addr, opcode));
+            addr += len;
+            continue;
+        }
+
+        let data_start = addr;
+        let mut data = Vec::new();
+        while (addr as usize) < mem.len() && !instructions.contains_key(&addr) && data.len() < DATA_PER_LINE {
+            data.push(mem[addr as usize]);
+            addr += 1;
+        }
+
+        lines.push(Line::Data(data_start, data));
+    }
+
+    lines
+}
+
+fn mode_name(val: &Value) -> &'static str {
+    match val {
+        Value::Position(_) => "P",
+        Value::Immediate(_) => "I",
+        Value::Relative(_) => "R",
+    }
+}
+
+fn describe_jump(target: &Value, mem: &[isize]) -> String {
+    match *target {
+        Value::Immediate(target) => format!("jumps to {}", target),
+        Value::Position(addr) => match mem.get(addr as usize).filter(|_| addr >= 0) {
+            Some(target) => format!("jumps to mem[{}] = {}", addr, target),
+            None => format!("jumps to mem[{}]", addr),
+        },
+        Value::Relative(_) => format!("jumps to {}", target),
+    }
+}
+
+/// the listing of a program, one line per instruction or run of data.
+pub struct Listing<'a> {
+    mem: &'a [isize],
+    lines: Vec<Line>,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(mem: &'a [isize], instructions: &InstructionSet) -> Listing<'a> {
+        Listing {
+            mem,
+            lines: disassemble(mem, instructions),
+        }
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Instruction(addr, opcode) => {
+                    let params = opcode.params();
+                    if params.is_empty() {
+                        writeln!(f, "{:>6}: {}", addr, opcode)?;
+                        continue;
+                    }
+
+                    let modes: Vec<&str> = params.iter().map(|val| mode_name(val)).collect();
+                    let mut annotation = format!("modes {}", modes.join(" "));
+
+                    if let Opcode::JumpIfTrue(_, target) | Opcode::JumpIfFalse(_, target) = opcode {
+                        annotation = format!("{}, {}", annotation, describe_jump(target, self.mem));
+                    }
+
+                    writeln!(f, "{:>6}: {:<32} ; {}", addr, opcode.to_string(), annotation)?;
+                }
+
+                Line::Data(addr, data) => {
+                    let data: Vec<String> = data.iter().map(|word| word.to_string()).collect();
+                    writeln!(f, "{:>6}: data {}", addr, data.join(", "))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn separates_code_from_data() {
+    //calls a function that doubles its input, then outputs the result
+    let src = "
+            arb @stack
+            in [rb+1]
+            add @ret, 0, [rb]
+            jt 1, @double
+    ret:    out [rb+1]
+            halt
+    double: mul [rb+1], 2, [rb+1]
+            jf 0, [rb]
+    stack:  data 0, 0
+    ";
+    let mem = crate::intcode::assembler::assemble(src).unwrap();
+
+    let expected = "     0: arb 21                           ; modes I
+     2: in [rb+1]                        ; modes R
+     4: add 11, 0, [rb]                  ; modes I I R
+     8: jt 1, 14                         ; modes I I, jumps to 14
+    11: out [rb+1]                       ; modes R
+    13: halt
+    14: mul [rb+1], 2, [rb+1]            ; modes R I R
+    18: jf 0, [rb]                       ; modes I R, jumps to [rb]
+    21: data 0, 0
+";
+
+    assert_eq!(Listing::new(&mem, &InstructionSet::standard()).to_string(), expected);
+}
+
+#[test]
+fn decodes_extensions() {
+    use crate::intcode::extensions::reboot;
+
+    let mem = crate::intcode::assembler::assemble("out 1\nreboot").unwrap();
+    let mut instructions = InstructionSet::standard();
+    instructions.register(reboot());
+
+    let lines = disassemble(&mem, &instructions);
+    assert!(matches!(&lines[1], Line::Instruction(2, opcode) if *opcode == reboot().opcode(vec![])));
+    assert_eq!(successors(2, &reboot().opcode(vec![])), vec![0]);
+
+    //without the extension, opcode 0 is just data
+    let lines = disassemble(&mem, &InstructionSet::standard());
+    assert!(matches!(&lines[1], Line::Data(2, data) if *data == vec![0]));
+}
@@ -0,0 +1,143 @@
+//! extension opcodes: instructions outside the standard set, which a runner only decodes once
+//! they're registered in its InstructionSet. with the standard set, opcodes other than 1-9 and 99,
+//! including 0, fault with UnsupportedOpcode.
+//!
+//! an Extension is the simplest kind of Instruction: a two-digit opcode, a mnemonic for the
+//! disassembler, an arity of up to 3 parameters that take the usual parameter modes, and a function
+//! executing it. the function gets the decoded parameters, which it can evaluate with
+//! eval_interpret(), eval_literal() and write_param(), and returns the address to continue at, or
+//! None to continue after the instruction.
+//!
+//! since an extension may jump anywhere, analysis::Cfg ends a block at it. only the interpreter
+//! runs extensions: compiled programs fall back to it for them.
+//!
+//! the one extension provided is reboot(), opcode 0, which day13 relies on.
+
+use crate::intcode::{Flow, Instruction, IntcodeError, IntcodeRunner, Opcode, Value, Word};
+
+/// the opcode of the Reboot extension.
+pub const REBOOT: isize = 0;
+
+/// executes an extension given its parameters. returns the address to continue at, if it jumps.
+pub type ExecFn<W> = fn(&mut IntcodeRunner<W>, &[Value<W>]) -> Result<Option<isize>, IntcodeError<W>>;
+
+#[derive(Clone, Copy)]
+pub struct Extension<W = isize> {
+    pub code: isize,
+    pub mnemonic: &'static str,
+    pub arity: usize,
+    pub exec: ExecFn<W>,
+}
+
+impl<W: Word> Extension<W> {
+    /// the instruction running this extension with the given parameters.
+    /// panics if the number of parameters isn't the extension's arity.
+    pub fn opcode(&self, params: Vec<Value<W>>) -> Opcode<W> {
+        assert_eq!(params.len(), self.arity, "`{}` takes {} parameters", self.mnemonic, self.arity);
+
+        Opcode::Extension(ExtOp {
+            code: self.code,
+            mnemonic: self.mnemonic,
+            params,
+        })
+    }
+}
+
+/// a decoded instruction outside the standard set.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ExtOp<W = isize> {
+    pub code: isize,
+    pub mnemonic: &'static str,
+    pub params: Vec<Value<W>>,
+}
+
+impl<W: Word> Instruction<W> for Extension<W> {
+    fn code(&self) -> isize {
+        self.code
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn decode(&self, params: [Value<W>; 3]) -> Opcode<W> {
+        let mut params = params.to_vec();
+        params.truncate(self.arity);
+        self.opcode(params)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        let params: Vec<Value<W>> = opcode.params().into_iter().cloned().collect();
+
+        match (self.exec)(runner, &params)? {
+            Some(target) => Ok(Flow::Jump(target)),
+            None => Ok(Flow::Next),
+        }
+    }
+}
+
+/// opcode 0: restarts the program from address 0 with the relative base, inputs and memory past
+/// the end of the program cleared, but keeps the program's own memory as it is.
+pub fn reboot<W: Word>() -> Extension<W> {
+    Extension {
+        code: REBOOT,
+        mnemonic: "reboot",
+        arity: 0,
+        exec: |runner, _| {
+            runner.offset = 0;
+            runner.inputs.clear();
+            runner.mem.clear_extra();
+            runner.halted = false;
+            runner.journal_clear();
+            Ok(Some(0))
+        },
+    }
+}
+
+#[test]
+fn opcode_0_needs_reboot() {
+    //outputs 7, then hits opcode 0 with 7 queued as input
+    let mem = vec![104, 7, 0];
+
+    let mut runner = IntcodeRunner::new(&mem);
+    assert_eq!(runner.run(), Ok(crate::intcode::RunState::Output(7)));
+    assert!(matches!(runner.run(), Err(IntcodeError::UnsupportedOpcode(_))));
+
+    let mut instructions = crate::intcode::InstructionSet::standard();
+    instructions.register(reboot());
+    runner.set_instructions(instructions);
+    runner.push_input(7);
+
+    assert_eq!(runner.parse_cur_opcode(), Ok(reboot().opcode(vec![])));
+    runner.step().unwrap();
+    assert_eq!(runner.inst_ptr(), 0);
+    assert_eq!(runner.inputs.len(), 0);
+}
+
+#[test]
+fn runs_custom_extensions() {
+    //opcode 42 writes the sum of the squares of its first two parameters to the third
+    let square_sum = Extension {
+        code: 42,
+        mnemonic: "sqsum",
+        arity: 3,
+        exec: |runner, params| {
+            let a = runner.eval_interpret(params[0].clone())?;
+            let b = runner.eval_interpret(params[1].clone())?;
+            runner.write_param(params[2].clone(), a * a + b * b)?;
+            Ok(None)
+        },
+    };
+
+    let mut instructions = crate::intcode::InstructionSet::standard();
+    instructions.register(square_sum);
+
+    let mut runner = IntcodeRunner::new(&[1142, 3, 4, 7, 4, 7, 99, 0]);
+    runner.set_instructions(instructions);
+    assert_eq!(runner.run(), Ok(crate::intcode::RunState::Output(25)));
+    assert_eq!(runner.parse_cur_opcode().unwrap().to_string(), "halt");
+}
@@ -0,0 +1,24 @@
+//! small programs shared by the tests of several modules.
+
+use crate::intcode::assembler::assemble;
+
+/// outputs double its input until the input is 0.
+pub fn doubler() -> Vec<isize> {
+    let src = "
+    loop:   in x
+            jf [x], @end
+            mul [x], 2, [x]
+            out [x]
+            jt 1, @loop
+    end:    halt
+    x:      data 0
+    ";
+
+    assemble(src).unwrap()
+}
+
+/// day9's quine, which uses relative mode and memory past the end of the program.
+pub const QUINE: &[isize] = &[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];
+
+/// writes 99 over the Out instruction at 4, so it halts there without any output.
+pub const OVERWRITES_OUT: &[isize] = &[1101, 0, 99, 4, 104, 5, 99];
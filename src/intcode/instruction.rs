@@ -0,0 +1,630 @@
+//! the instruction set, as a registry of Instructions keyed by their two-digit opcode.
+//!
+//! an Instruction knows its arity, how to build the decoded Opcode from its parameters, and how to
+//! execute it. the standard set, 1-9 and 99, is implemented on top of it, and is what runners use
+//! unless they're given an InstructionSet of their own, which can add instructions, replace
+//! standard ones or remove them, to try out Intcode dialects.
+//!
+//! the decoded form of an instruction is still an Opcode, which is what the disassembler, the
+//! analysis, traces and the compiler work with. instructions outside the standard set decode to
+//! Opcode::Extension, see extensions.rs for a simple way to write them.
+//!
+//! compiled and pre-decoded instructions only know the standard set, so a runner given its own
+//! InstructionSet decodes and interprets every instruction.
+
+use crate::intcode::{IntcodeError, IntcodeRunner, Opcode, RunState, Value, Word};
+use std::sync::Arc;
+
+/// where execution goes after an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flow<W = isize> {
+    /// on to the next instruction.
+    Next,
+    Jump(isize),
+    /// on to the next instruction, but stop running and report this.
+    Report(RunState<W>),
+}
+
+pub trait Instruction<W>: Send + Sync {
+    /// the two-digit opcode.
+    fn code(&self) -> isize;
+
+    fn mnemonic(&self) -> &'static str;
+
+    /// how many parameters follow the opcode, at most 3.
+    fn arity(&self) -> usize;
+
+    /// the decoded instruction, given the three words following the opcode parsed as parameters.
+    /// only the first arity() of them are parameters of this instruction, and the opcode returned
+    /// has to have exactly that many, or decoding faults with WrongArity.
+    fn decode(&self, params: [Value<W>; 3]) -> Opcode<W>;
+
+    /// executes the instruction `opcode`, which was decoded by decode(). on error, the instruction
+    /// should leave the runner as it was, except for words it already wrote.
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>>;
+}
+
+#[derive(Clone)]
+enum Slot<W> {
+    /// the standard instruction with the slot's opcode.
+    Standard,
+    Custom(Arc<dyn Instruction<W>>),
+}
+
+/// the instructions a runner decodes, by opcode.
+#[derive(Clone)]
+pub struct InstructionSet<W = isize> {
+    slots: Vec<Option<Slot<W>>>,
+}
+
+impl<W: Word> InstructionSet<W> {
+    /// an instruction set without any instructions.
+    pub fn new() -> InstructionSet<W> {
+        InstructionSet { slots: vec![None; 100] }
+    }
+
+    /// the standard instructions, 1-9 and 99.
+    pub fn standard() -> InstructionSet<W> {
+        InstructionSet {
+            slots: (0..100).map(|code| standard::<W>(code).map(|_| Slot::Standard)).collect(),
+        }
+    }
+
+    /// adds an instruction, replacing any with the same opcode.
+    /// panics if its opcode isn't two digits or it takes more than 3 parameters.
+    pub fn register(&mut self, instruction: impl Instruction<W> + 'static) {
+        let code = instruction.code();
+        assert!((0..100).contains(&code), "opcode {} isn't two digits", code);
+        assert!(instruction.arity() <= 3, "instructions take at most 3 parameters");
+
+        self.slots[code as usize] = Some(Slot::Custom(Arc::new(instruction)));
+    }
+
+    /// removes the instruction with opcode `code`. returns false if there's none.
+    pub fn remove(&mut self, code: isize) -> bool {
+        match self.slots.get_mut(code as usize) {
+            Some(slot) if code >= 0 => slot.take().is_some(),
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, code: isize) -> Option<&dyn Instruction<W>> {
+        if code < 0 {
+            return None;
+        }
+
+        match self.slots.get(code as usize)? {
+            Some(Slot::Standard) => standard(code),
+            Some(Slot::Custom(instruction)) => Some(instruction.as_ref()),
+            None => None,
+        }
+    }
+
+    /// every instruction, ordered by opcode.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Instruction<W>> {
+        (0..100).filter_map(move |code| self.get(code))
+    }
+}
+
+impl<W: Word> Default for InstructionSet<W> {
+    fn default() -> Self {
+        InstructionSet::standard()
+    }
+}
+
+/// the standard instruction with opcode `code`, if there's one.
+pub fn standard<W: Word>(code: isize) -> Option<&'static dyn Instruction<W>> {
+    let instruction: &'static dyn Instruction<W> = match code {
+        1 => &Add,
+        2 => &Mul,
+        3 => &Input,
+        4 => &Output,
+        5 => &JumpIfTrue,
+        6 => &JumpIfFalse,
+        7 => &LessThan,
+        8 => &Equals,
+        9 => &AdjustBase,
+        99 => &Halt,
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+/// executes `opcode` with the runner's instruction set.
+pub(super) fn exec<W: Word>(runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+    let code = opcode.code();
+
+    //custom instructions are kept alive separately, since they need the runner
+    let custom = match &runner.instructions {
+        Some(instructions) => match instructions.slots.get(code as usize).filter(|_| code >= 0) {
+            Some(Some(Slot::Custom(instruction))) => {
+                check_arity(runner, opcode, instruction.arity())?;
+                Some(Arc::clone(instruction))
+            }
+            Some(Some(Slot::Standard)) => None,
+            _ => return Err(IntcodeError::UnsupportedOpcode(runner.fault())),
+        },
+        None => None,
+    };
+
+    match custom {
+        Some(instruction) => instruction.exec(runner, opcode),
+        None => exec_standard(runner, opcode),
+    }
+}
+
+/// executes `opcode` with the standard instruction its variant stands for. calling them directly
+/// rather than through the registry is quite a bit faster.
+fn exec_standard<W: Word>(runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+    match opcode {
+        Opcode::Add(_, _, _) => Add.exec(runner, opcode),
+        Opcode::Mul(_, _, _) => Mul.exec(runner, opcode),
+        Opcode::In(_) => Input.exec(runner, opcode),
+        Opcode::Out(_) => Output.exec(runner, opcode),
+        Opcode::JumpIfTrue(_, _) => JumpIfTrue.exec(runner, opcode),
+        Opcode::JumpIfFalse(_, _) => JumpIfFalse.exec(runner, opcode),
+        Opcode::LT(_, _, _) => LessThan.exec(runner, opcode),
+        Opcode::EQ(_, _, _) => Equals.exec(runner, opcode),
+        Opcode::BaseOffset(_) => AdjustBase.exec(runner, opcode),
+        Opcode::Halt => Halt.exec(runner, opcode),
+        Opcode::Extension(_) => Err(IntcodeError::UnsupportedOpcode(runner.fault())),
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// decodes and executes instructions from `instructions` from now on, instead of the standard
+    /// set. drops the instructions pre-decoded or compiled by the Program the runner was spawned
+    /// from, which only know the standard set.
+    pub fn set_instructions(&mut self, instructions: InstructionSet<W>) {
+        self.instructions = Some(Arc::new(instructions));
+        self.decoded = None;
+        self.compiled = None;
+    }
+
+    /// the runner's own instruction set, if it was given one.
+    pub fn instructions(&self) -> Option<&InstructionSet<W>> {
+        self.instructions.as_deref()
+    }
+
+    /// writes `word` to the address given by a parameter, like instructions writing their result.
+    pub fn write_param(&mut self, val: Value<W>, word: W) -> Result<(), IntcodeError<W>> {
+        let addr = self.eval_literal(val)?;
+        self.set_mem(addr, word)
+    }
+}
+
+/// faults with WrongArity unless `opcode` has `arity` parameters.
+fn check_arity<W: Word>(runner: &IntcodeRunner<W>, opcode: &Opcode<W>, arity: usize) -> Result<(), IntcodeError<W>> {
+    let found = opcode.num_vals() as usize;
+    if found == arity {
+        return Ok(());
+    }
+
+    Err(IntcodeError::WrongArity {
+        code: opcode.code(),
+        arity,
+        found,
+        fault: runner.fault(),
+    })
+}
+
+/// the parameters of a decoded instruction taking `N` of them, for implementing exec().
+pub fn params<W: Word, const N: usize>(runner: &IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<[Value<W>; N], IntcodeError<W>> {
+    check_arity(runner, opcode, N)?;
+
+    Ok(std::array::from_fn(|idx| opcode.param(idx).expect("the parameter count was checked").clone()))
+}
+
+/// Add and Mul.
+fn arithmetic<W: Word>(
+    runner: &mut IntcodeRunner<W>,
+    opcode: &Opcode<W>,
+    op: fn(W, W) -> Option<W>,
+) -> Result<Flow<W>, IntcodeError<W>> {
+    let [val1, val2, val3] = params(runner, opcode)?;
+
+    let op1 = runner.eval_interpret(val1)?;
+    let op2 = runner.eval_interpret(val2)?;
+    let addr = runner.eval_literal(val3)?;
+    let res = op(op1, op2).ok_or_else(|| IntcodeError::Overflow(runner.fault()))?;
+    runner.set_mem(addr, res)?;
+
+    Ok(Flow::Next)
+}
+
+/// JumpIfTrue and JumpIfFalse.
+fn jump_if<W: Word>(runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>, when: bool) -> Result<Flow<W>, IntcodeError<W>> {
+    let [val1, val2] = params(runner, opcode)?;
+
+    if (runner.eval_interpret(val1)? != W::default()) != when {
+        return Ok(Flow::Next);
+    }
+
+    let target = runner.eval_interpret(val2)?;
+    Ok(Flow::Jump(runner.to_addr(target)?))
+}
+
+/// LessThan and Equals.
+fn compare<W: Word>(
+    runner: &mut IntcodeRunner<W>,
+    opcode: &Opcode<W>,
+    cmp: fn(&W, &W) -> bool,
+) -> Result<Flow<W>, IntcodeError<W>> {
+    let [val1, val2, val3] = params(runner, opcode)?;
+
+    let comparison_res = cmp(&runner.eval_interpret(val1)?, &runner.eval_interpret(val2)?) as isize;
+    let addr = runner.eval_literal(val3)?;
+    runner.set_mem(addr, W::from_isize(comparison_res))?;
+
+    Ok(Flow::Next)
+}
+
+pub struct Add;
+
+impl<W: Word> Instruction<W> for Add {
+    fn code(&self) -> isize {
+        1
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "add"
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn decode(&self, [val1, val2, val3]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::Add(val1, val2, val3)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        arithmetic(runner, opcode, W::sum)
+    }
+}
+
+pub struct Mul;
+
+impl<W: Word> Instruction<W> for Mul {
+    fn code(&self) -> isize {
+        2
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "mul"
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn decode(&self, [val1, val2, val3]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::Mul(val1, val2, val3)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        arithmetic(runner, opcode, W::product)
+    }
+}
+
+pub struct Input;
+
+impl<W: Word> Instruction<W> for Input {
+    fn code(&self) -> isize {
+        3
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "in"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn decode(&self, [val, _, _]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::In(val)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        let [val] = params(runner, opcode)?;
+
+        let input = runner.peek_next_input()?;
+        let addr = runner.eval_literal(val)?;
+        runner.set_mem(addr, input)?;
+        runner.consume_input();
+
+        Ok(Flow::Next)
+    }
+}
+
+pub struct Output;
+
+impl<W: Word> Instruction<W> for Output {
+    fn code(&self) -> isize {
+        4
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "out"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn decode(&self, [val, _, _]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::Out(val)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        let [val] = params(runner, opcode)?;
+
+        let output = runner.eval_interpret(val)?;
+        runner.produce_output(output);
+
+        Ok(Flow::Report(RunState::Output(output)))
+    }
+}
+
+pub struct JumpIfTrue;
+
+impl<W: Word> Instruction<W> for JumpIfTrue {
+    fn code(&self) -> isize {
+        5
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "jt"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn decode(&self, [val1, val2, _]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::JumpIfTrue(val1, val2)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        jump_if(runner, opcode, true)
+    }
+}
+
+pub struct JumpIfFalse;
+
+impl<W: Word> Instruction<W> for JumpIfFalse {
+    fn code(&self) -> isize {
+        6
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "jf"
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn decode(&self, [val1, val2, _]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::JumpIfFalse(val1, val2)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        jump_if(runner, opcode, false)
+    }
+}
+
+pub struct LessThan;
+
+impl<W: Word> Instruction<W> for LessThan {
+    fn code(&self) -> isize {
+        7
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "lt"
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn decode(&self, [val1, val2, val3]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::LT(val1, val2, val3)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        compare(runner, opcode, W::lt)
+    }
+}
+
+pub struct Equals;
+
+impl<W: Word> Instruction<W> for Equals {
+    fn code(&self) -> isize {
+        8
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "eq"
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn decode(&self, [val1, val2, val3]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::EQ(val1, val2, val3)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        compare(runner, opcode, W::eq)
+    }
+}
+
+pub struct AdjustBase;
+
+impl<W: Word> Instruction<W> for AdjustBase {
+    fn code(&self) -> isize {
+        9
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "arb"
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn decode(&self, [val, _, _]: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::BaseOffset(val)
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, opcode: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        let [val] = params(runner, opcode)?;
+
+        let offset_change = runner.eval_interpret(val)?;
+        runner.offset = runner.relative_addr(offset_change)?;
+
+        Ok(Flow::Next)
+    }
+}
+
+pub struct Halt;
+
+impl<W: Word> Instruction<W> for Halt {
+    fn code(&self) -> isize {
+        99
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        "halt"
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn decode(&self, _: [Value<W>; 3]) -> Opcode<W> {
+        Opcode::Halt
+    }
+
+    fn exec(&self, runner: &mut IntcodeRunner<W>, _: &Opcode<W>) -> Result<Flow<W>, IntcodeError<W>> {
+        runner.halted = true;
+        Ok(Flow::Report(RunState::Halted))
+    }
+}
+
+#[test]
+fn standard_set_matches_opcodes() {
+    let set = InstructionSet::<isize>::standard();
+    let codes: Vec<isize> = set.iter().map(|instruction| instruction.code()).collect();
+    assert_eq!(codes, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 99]);
+
+    for instruction in set.iter() {
+        let opcode = instruction.decode([Value::Position(1), Value::Immediate(2), Value::Relative(3)]);
+        assert_eq!(opcode.code(), instruction.code());
+        assert_eq!(opcode.mnemonic(), instruction.mnemonic());
+        assert_eq!(opcode.params().len(), instruction.arity());
+    }
+}
+
+#[test]
+fn runs_dialects() {
+    //subtracts instead of adding, and has no Mul
+    struct Sub;
+
+    impl Instruction<isize> for Sub {
+        fn code(&self) -> isize {
+            1
+        }
+
+        fn mnemonic(&self) -> &'static str {
+            "sub"
+        }
+
+        fn arity(&self) -> usize {
+            3
+        }
+
+        fn decode(&self, params: [Value; 3]) -> Opcode {
+            Opcode::Extension(crate::intcode::ExtOp {
+                code: 1,
+                mnemonic: "sub",
+                params: params.to_vec(),
+            })
+        }
+
+        fn exec(&self, runner: &mut IntcodeRunner, opcode: &Opcode) -> Result<Flow, IntcodeError> {
+            let [val1, val2, val3] = params(runner, opcode)?;
+            let diff = runner.eval_interpret(val1)? - runner.eval_interpret(val2)?;
+            runner.write_param(val3, diff)?;
+            Ok(Flow::Next)
+        }
+    }
+
+    let mut dialect = InstructionSet::standard();
+    dialect.register(Sub);
+    assert!(dialect.remove(2));
+
+    let mut runner = IntcodeRunner::new(&[1101, 10, 3, 7, 4, 7, 2, 0]);
+    runner.set_instructions(dialect);
+    assert_eq!(runner.run(), Ok(RunState::Output(7)));
+    assert!(matches!(runner.run(), Err(IntcodeError::UnsupportedOpcode(_))));
+}
+
+#[test]
+fn rejects_wrong_arity() {
+    use crate::intcode::ExtOp;
+
+    //claims two parameters, but decodes with all three
+    struct Sloppy;
+
+    impl Instruction<isize> for Sloppy {
+        fn code(&self) -> isize {
+            20
+        }
+
+        fn mnemonic(&self) -> &'static str {
+            "slop"
+        }
+
+        fn arity(&self) -> usize {
+            2
+        }
+
+        fn decode(&self, params: [Value; 3]) -> Opcode {
+            Opcode::Extension(ExtOp {
+                code: 20,
+                mnemonic: "slop",
+                params: params.to_vec(),
+            })
+        }
+
+        fn exec(&self, _: &mut IntcodeRunner, _: &Opcode) -> Result<Flow, IntcodeError> {
+            Ok(Flow::Next)
+        }
+    }
+
+    let mut dialect = InstructionSet::standard();
+    dialect.register(Sloppy);
+
+    let mut runner = IntcodeRunner::new(&[20, 0, 0, 99]);
+    runner.set_instructions(dialect);
+    let error = runner.run().unwrap_err();
+    assert!(matches!(error, IntcodeError::WrongArity { code: 20, arity: 2, found: 3, .. }));
+    assert_eq!(error.to_string(), "opcode 20 takes 2 parameters, but was given 3 (instruction pointer 0, relative base 0)");
+
+    let opcode = Opcode::Extension(ExtOp {
+        code: 20,
+        mnemonic: "slop",
+        params: vec![Value::Immediate(1)],
+    });
+    assert!(matches!(
+        runner.exec_opcode(opcode),
+        Err(IntcodeError::WrongArity { code: 20, arity: 2, found: 1, .. })
+    ));
+}
@@ -0,0 +1,211 @@
+//! reverse execution, by keeping an undo journal of the last instructions executed.
+//!
+//! while journaling, every completed instruction leaves an entry holding what it changed: the
+//! instruction pointer, relative base, last output and halted flag from before it ran, the old
+//! value of every word it wrote, and the input it consumed. undoing an entry restores all of those.
+//!
+//! the journal only keeps the most recent entries, up to its limit. a Reboot can't be undone, so it
+//! clears the journal. traces, watchpoint logs and recorded sessions aren't rolled back.
+
+use crate::intcode::{InputMode, IntcodeRunner, Word};
+use std::collections::VecDeque;
+
+#[derive(Clone)]
+struct Entry<W> {
+    inst_ptr: isize,
+    offset: isize,
+    output: Option<W>,
+    halted: bool,
+    /// the old value of every word written, in the order they were written.
+    writes: Vec<(usize, W)>,
+    /// the input consumed from the queue, if any.
+    input: Option<W>,
+}
+
+#[derive(Clone)]
+pub(super) struct Journal<W> {
+    entries: VecDeque<Entry<W>>,
+    limit: usize,
+    /// the entry of the instruction being executed.
+    current: Option<Entry<W>>,
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// starts journaling, so that up to `limit` instructions can be undone.
+    /// restarting drops the journal kept so far.
+    pub fn start_journal(&mut self, limit: usize) {
+        self.journal = Some(Journal {
+            entries: VecDeque::new(),
+            limit,
+            current: None,
+        });
+    }
+
+    pub fn stop_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// how many instructions can currently be undone.
+    pub fn journal_len(&self) -> usize {
+        self.journal.as_ref().map_or(0, |journal| journal.entries.len())
+    }
+
+    /// undoes the last `n` instructions, or as many as the journal holds.
+    /// returns how many were undone.
+    pub fn step_back(&mut self, n: usize) -> usize {
+        for undone in 0..n {
+            if !self.undo() {
+                return undone;
+            }
+        }
+
+        n
+    }
+
+    /// undoes instructions up to and including the last one that wrote to `addr`, so that it's
+    /// the next instruction to run. leaves the runner as it is and returns false if no journaled
+    /// instruction wrote to `addr`.
+    pub fn step_back_to_write(&mut self, addr: usize) -> bool {
+        let count = match &self.journal {
+            Some(journal) => journal
+                .entries
+                .iter()
+                .rev()
+                .position(|entry| entry.writes.iter().any(|&(a, _)| a == addr)),
+            None => None,
+        };
+
+        match count {
+            Some(count) => self.step_back(count + 1) == count + 1,
+            None => false,
+        }
+    }
+
+    fn undo(&mut self) -> bool {
+        let entry = match self.journal.as_mut().and_then(|journal| journal.entries.pop_back()) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        for &(addr, old_val) in entry.writes.iter().rev() {
+            self.write(addr, old_val);
+        }
+
+        if let Some(input) = entry.input {
+            self.inputs.push_back(input);
+        }
+
+        self.inst_ptr = entry.inst_ptr;
+        self.offset = entry.offset;
+        self.output = entry.output;
+        self.halted = entry.halted;
+
+        true
+    }
+
+    pub(super) fn journal_begin(&mut self) {
+        if self.journal.is_none() {
+            return;
+        }
+
+        let entry = Entry {
+            inst_ptr: self.inst_ptr,
+            offset: self.offset,
+            output: self.output,
+            halted: self.halted,
+            writes: Vec::new(),
+            input: None,
+        };
+
+        if let Some(journal) = &mut self.journal {
+            journal.current = Some(entry);
+        }
+    }
+
+    /// files the entry of the instruction just executed, unless it didn't complete.
+    pub(super) fn journal_end(&mut self, completed: bool) {
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            None => return,
+        };
+
+        if let Some(entry) = journal.current.take().filter(|_| completed) {
+            if journal.entries.len() == journal.limit {
+                journal.entries.pop_front();
+            }
+
+            if journal.limit > 0 {
+                journal.entries.push_back(entry);
+            }
+        }
+    }
+
+    /// notes the old value of a word the current instruction is about to write.
+    pub(super) fn journal_write(&mut self, addr: usize) {
+        if let Some(Journal { current: Some(_), .. }) = &self.journal {
+            let old_val = self.read(addr);
+            if let Some(entry) = self.journal.as_mut().and_then(|journal| journal.current.as_mut()) {
+                entry.writes.push((addr, old_val));
+            }
+        }
+    }
+
+    /// notes the input the current instruction is about to consume.
+    pub(super) fn journal_input(&mut self, input: W) {
+        if let InputMode::SingleInput = self.input_mode {
+            return;
+        }
+
+        if let Some(entry) = self.journal.as_mut().and_then(|journal| journal.current.as_mut()) {
+            entry.input = Some(input);
+        }
+    }
+
+    /// forgets everything journaled, e.g. because the runner rebooted.
+    pub(super) fn journal_clear(&mut self) {
+        if let Some(journal) = &mut self.journal {
+            journal.entries.clear();
+            journal.current = None;
+        }
+    }
+}
+
+#[test]
+fn steps_back_and_forth() {
+    let mut runner = IntcodeRunner::new(&crate::intcode::fixtures::doubler());
+    runner.start_journal(100);
+    runner.push_input(0);
+    runner.push_input(5);
+
+    let outputs: Vec<isize> = runner.outputs().collect::<Result<_, _>>().unwrap();
+    assert_eq!(outputs, vec![10]);
+    assert!(runner.has_halted());
+
+    //halt, jf, in, jt, out, mul, jf, in
+    assert_eq!(runner.step_back(100), 8);
+    assert_eq!(runner.inst_ptr(), 0);
+    assert_eq!(runner.read(15), 0);
+    assert_eq!(runner.output(), None);
+    assert!(!runner.has_halted());
+
+    let outputs: Vec<isize> = runner.outputs().collect::<Result<_, _>>().unwrap();
+    assert_eq!(outputs, vec![10]);
+}
+
+#[test]
+fn steps_back_to_last_write() {
+    //adds 1 to [12] twice, then halts
+    let mem = vec![1001, 12, 1, 12, 1001, 12, 1, 12, 99, 0, 0, 0, 0];
+
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.start_journal(10);
+    runner.run().unwrap();
+    assert_eq!(runner.read(12), 2);
+
+    assert!(runner.step_back_to_write(12));
+    assert_eq!(runner.inst_ptr(), 4);
+    assert_eq!(runner.read(12), 1);
+
+    assert!(!runner.step_back_to_write(11));
+    assert_eq!(runner.inst_ptr(), 4);
+}
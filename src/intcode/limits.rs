@@ -0,0 +1,132 @@
+//! upper bounds on how long a runner may run: a budget of instructions, and a wall-clock deadline.
+//!
+//! once either is exhausted, step() and run() return RunState::LimitReached without executing
+//! the next instruction, every time they're called, until the limit is raised or removed. only
+//! instructions that complete count against the budget, so asking for input costs nothing.
+//!
+//! reading the clock isn't free, so the deadline is only checked every CLOCK_INTERVAL instructions.
+
+use crate::intcode::{IntcodeRunner, Word};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// how many instructions run between checks of the deadline.
+pub const CLOCK_INTERVAL: u32 = 1024;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    /// the step budget ran out.
+    Steps,
+    /// the deadline passed.
+    Deadline,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Steps => write!(f, "step budget exhausted"),
+            Limit::Deadline => write!(f, "deadline passed"),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub(super) struct Limits {
+    /// how many more instructions may run.
+    budget: Option<u64>,
+    deadline: Option<Instant>,
+    /// instructions left until the deadline is checked again.
+    until_clock: u32,
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// allows `steps` more instructions to run, or any number if None.
+    pub fn set_step_budget(&mut self, steps: Option<u64>) {
+        self.limits.budget = steps;
+    }
+
+    /// how many more instructions may run, if there's a budget.
+    pub fn step_budget(&self) -> Option<u64> {
+        self.limits.budget
+    }
+
+    /// stops the runner once `deadline` has passed, or removes the deadline if None.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.limits.deadline = deadline;
+        self.limits.until_clock = 0;
+    }
+
+    /// sets the deadline to `time` from now.
+    pub fn set_time_limit(&mut self, time: Duration) {
+        self.set_deadline(Some(Instant::now() + time));
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.limits.deadline
+    }
+
+    /// the limit that keeps the next instruction from running, if any.
+    pub(super) fn limit_reached(&mut self) -> Option<Limit> {
+        let limits = &mut self.limits;
+
+        if limits.budget == Some(0) {
+            return Some(Limit::Steps);
+        }
+
+        if let Some(deadline) = limits.deadline {
+            if limits.until_clock == 0 {
+                if Instant::now() >= deadline {
+                    return Some(Limit::Deadline);
+                }
+
+                limits.until_clock = CLOCK_INTERVAL;
+            }
+
+            limits.until_clock -= 1;
+        }
+
+        None
+    }
+
+    /// counts a completed instruction against the budget.
+    pub(super) fn charge_step(&mut self) {
+        if let Some(budget) = &mut self.limits.budget {
+            *budget -= 1;
+        }
+    }
+}
+
+#[test]
+fn stops_runaway_programs() {
+    use crate::intcode::RunState;
+
+    //loops forever
+    let mem = vec![1105, 1, 0];
+
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.set_step_budget(Some(10));
+    assert_eq!(runner.run(), Ok(RunState::LimitReached(Limit::Steps)));
+    assert_eq!(runner.run(), Ok(RunState::LimitReached(Limit::Steps)));
+    assert_eq!(runner.step_budget(), Some(0));
+
+    runner.set_step_budget(None);
+    runner.set_time_limit(Duration::from_millis(10));
+    assert_eq!(runner.run(), Ok(RunState::LimitReached(Limit::Deadline)));
+}
+
+#[test]
+fn waiting_for_input_is_free() {
+    use crate::intcode::RunState;
+
+    //outputs its input plus 1
+    let mem = vec![3, 9, 101, 1, 9, 9, 4, 9, 99, 0];
+
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.set_step_budget(Some(3));
+    assert_eq!(runner.run(), Ok(RunState::NeedsInput));
+    assert_eq!(runner.step_budget(), Some(3));
+
+    runner.push_input(41);
+    assert_eq!(runner.run(), Ok(RunState::Output(42)));
+    assert_eq!(runner.run(), Ok(RunState::LimitReached(Limit::Steps)));
+}
@@ -0,0 +1,176 @@
+//! paged, copy-on-write memory, which makes cloning a runner cheap.
+//!
+//! memory is split into fixed-size pages. pages past the end of the program are only allocated
+//! once they're written to, and reading a page that was never written yields zeroes, so reads
+//! don't need to modify anything. pages are shared between clones: cloning only bumps a reference
+//! count, and the first write to a shared page copies just that page (along with the page table,
+//! the first time a clone writes at all).
+//!
+//! most programs stay close to their own code, so pages are looked up in a plain vector.
+//! only pages at absurdly high addresses go into a map, to keep the vector from exploding.
+
+use crate::intcode::Word;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub const PAGE_SIZE: usize = 256;
+
+/// pages below this index are kept in the page table vector.
+const NEAR_PAGES: usize = 1 << 16;
+
+type Page<W> = [W; PAGE_SIZE];
+
+#[derive(Clone)]
+pub struct Memory<W = isize> {
+    pages: Arc<Vec<Option<Arc<Page<W>>>>>,
+    far_pages: Arc<BTreeMap<usize, Arc<Page<W>>>>,
+    /// the length of the program the memory was created from.
+    len: usize,
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new(words: &[W]) -> Memory<W> {
+        let pages = words
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [W::default(); PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Some(Arc::new(page))
+            })
+            .collect();
+
+        Memory {
+            pages: Arc::new(pages),
+            far_pages: Arc::default(),
+            len: words.len(),
+        }
+    }
+
+    /// the length of the program the memory was created from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn page(&self, page_idx: usize) -> Option<&Page<W>> {
+        let page = if page_idx < NEAR_PAGES {
+            self.pages.get(page_idx).and_then(Option::as_ref)
+        } else {
+            self.far_pages.get(&page_idx)
+        };
+
+        page.map(|page| &**page)
+    }
+
+    fn page_mut(&mut self, page_idx: usize) -> &mut Page<W> {
+        let page = if page_idx < NEAR_PAGES {
+            let pages = Arc::make_mut(&mut self.pages);
+            if pages.len() <= page_idx {
+                pages.resize(page_idx + 1, None);
+            }
+
+            pages[page_idx].get_or_insert_with(|| Arc::new([W::default(); PAGE_SIZE]))
+        } else {
+            Arc::make_mut(&mut self.far_pages)
+                .entry(page_idx)
+                .or_insert_with(|| Arc::new([W::default(); PAGE_SIZE]))
+        };
+
+        Arc::make_mut(page)
+    }
+
+    pub fn get(&self, addr: usize) -> W {
+        self.page(addr / PAGE_SIZE)
+            .map(|page| page[addr % PAGE_SIZE])
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, addr: usize, val: W) {
+        self.page_mut(addr / PAGE_SIZE)[addr % PAGE_SIZE] = val;
+    }
+
+    /// zeroes everything past the end of the program.
+    pub fn clear_extra(&mut self) {
+        let program_pages = self.len.div_ceil(PAGE_SIZE);
+
+        if self.pages.len() > program_pages {
+            Arc::make_mut(&mut self.pages).truncate(program_pages);
+        }
+
+        if !self.far_pages.is_empty() {
+            self.far_pages = Arc::default();
+        }
+
+        let tail_start = self.len % PAGE_SIZE;
+        let tail_is_dirty = tail_start != 0
+            && self
+                .page(self.len / PAGE_SIZE)
+                .is_some_and(|page| page[tail_start..].iter().any(|&word| word != W::default()));
+
+        if tail_is_dirty {
+            for word in &mut self.page_mut(self.len / PAGE_SIZE)[tail_start..] {
+                *word = W::default();
+            }
+        }
+    }
+
+    /// the program's words, as modified so far.
+    pub fn to_vec(&self) -> Vec<W> {
+        (0..self.len).map(|addr| self.get(addr)).collect()
+    }
+
+    /// every non-zero word past the end of the program, by address.
+    pub fn extra_words(&self) -> Vec<(usize, W)> {
+        let near_pages = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(page_idx, page)| page.as_ref().map(|page| (page_idx, page)));
+
+        near_pages
+            .chain(self.far_pages.iter().map(|(&page_idx, page)| (page_idx, page)))
+            .flat_map(|(page_idx, page)| {
+                page.iter()
+                    .enumerate()
+                    .map(move |(idx, &word)| (page_idx * PAGE_SIZE + idx, word))
+            })
+            .filter(|&(addr, word)| addr >= self.len && word != W::default())
+            .collect()
+    }
+}
+
+#[test]
+fn clones_share_untouched_pages() {
+    let words: Vec<isize> = (0..PAGE_SIZE as isize * 3).collect();
+    let original: Memory = Memory::new(&words);
+
+    let mut fork = original.clone();
+    assert!(Arc::ptr_eq(&original.pages, &fork.pages));
+
+    fork.set(PAGE_SIZE + 1, -1);
+
+    assert_eq!(original.get(PAGE_SIZE + 1), PAGE_SIZE as isize + 1);
+    assert_eq!(fork.get(PAGE_SIZE + 1), -1);
+    assert!(Arc::ptr_eq(original.pages[0].as_ref().unwrap(), fork.pages[0].as_ref().unwrap()));
+    assert!(!Arc::ptr_eq(original.pages[1].as_ref().unwrap(), fork.pages[1].as_ref().unwrap()));
+    assert_eq!(original.to_vec(), words);
+}
+
+#[test]
+fn allocates_extra_pages_on_demand() {
+    let mut mem: Memory = Memory::new(&[1, 2, 3]);
+    let far = NEAR_PAGES * PAGE_SIZE * 10;
+
+    assert_eq!(mem.get(far), 0);
+    mem.set(5, 6);
+    mem.set(PAGE_SIZE * 4, 7);
+    mem.set(far, 8);
+    assert_eq!(mem.extra_words(), vec![(5, 6), (PAGE_SIZE * 4, 7), (far, 8)]);
+
+    mem.clear_extra();
+    assert_eq!(mem.extra_words(), vec![]);
+    assert_eq!(mem.to_vec(), vec![1, 2, 3]);
+}
@@ -0,0 +1,390 @@
+//! runs many machines round-robin, connected by addressed packets.
+//!
+//! every packet is three outputs: the destination address, then X and Y. packets to a machine in
+//! the network are queued for it, and it receives X and Y as its next two inputs. a machine that
+//! asks for input while its queue is empty receives -1 instead, and keeps running.
+//!
+//! the network is idle once every queue is empty and every machine has polled its empty queue
+//! IDLE_POLLS times in a row without sending anything. a Monitor sees every packet to an address
+//! outside the network, and is told when the network goes idle, so it can deliver packets of its
+//! own, like the NAT of 2019's day 23.
+//!
+//! a machine that pauses on a watchpoint or reaches a limit stops the whole network, so that the
+//! caller can look at it. running the network again resumes it. a machine that halts in the middle
+//! of sending a packet is an error.
+
+use crate::intcode::{IntcodeError, IntcodeRunner, Limit, Program, RunState, WatchHit, Word};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+/// how many times in a row a machine has to poll its empty queue to count as idle.
+pub const IDLE_POLLS: usize = 2;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Packet<W = isize> {
+    pub dest: W,
+    pub x: W,
+    pub y: W,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event<W = isize> {
+    /// a packet was sent to an address outside the network.
+    Unrouted(Packet<W>),
+    Idle,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action<W = isize> {
+    /// carry on, dropping the unrouted packet or letting the network stop as idle.
+    Continue,
+    /// deliver a packet instead.
+    Send(Packet<W>),
+    Stop,
+}
+
+pub trait Monitor<W> {
+    fn notify(&mut self, event: Event<W>) -> Action<W>;
+}
+
+impl<W, F: FnMut(Event<W>) -> Action<W>> Monitor<W> for F {
+    fn notify(&mut self, event: Event<W>) -> Action<W> {
+        self(event)
+    }
+}
+
+/// the reason the network stopped running.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NetState<W = isize> {
+    /// the network went idle and the monitor had nothing to send.
+    Idle,
+    /// the monitor stopped the network.
+    Stopped,
+    /// every machine has halted.
+    Halted,
+    /// a machine reached its step budget or deadline.
+    LimitReached { machine: usize, limit: Limit },
+    /// a machine hit a pausing watchpoint.
+    Watchpoint { machine: usize, hit: WatchHit<W> },
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NetworkError<W = isize> {
+    Fault { machine: usize, error: IntcodeError<W> },
+    /// a machine halted in the middle of sending a packet. holds the words it did send.
+    TruncatedPacket { machine: usize, words: Vec<W> },
+}
+
+impl<W: Word> fmt::Display for NetworkError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::Fault { machine, error } => write!(f, "machine {}: {}", machine, error),
+            NetworkError::TruncatedPacket { machine, words } => write!(
+                f,
+                "machine {}: halted after sending {} of a packet's 3 words",
+                machine,
+                words.len()
+            ),
+        }
+    }
+}
+
+impl<W: Word> Error for NetworkError<W> {}
+
+pub struct Network<W = isize> {
+    machines: Vec<IntcodeRunner<W>>,
+    queues: Vec<VecDeque<(W, W)>>,
+    /// the words of the packet each machine is in the middle of sending.
+    sending: Vec<Vec<W>>,
+    /// how many times in a row each machine has polled its empty queue.
+    polls: Vec<usize>,
+}
+
+impl<W: Word> Network<W> {
+    /// a network of the given machines, addressed by their index.
+    pub fn new(machines: Vec<IntcodeRunner<W>>) -> Network<W> {
+        let size = machines.len();
+
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            sending: vec![Vec::new(); size],
+            polls: vec![0; size],
+        }
+    }
+
+    /// `size` machines running `program`, each given its address as its first input.
+    pub fn boot(program: &Program<W>, size: usize) -> Network<W> {
+        let machines = (0..size)
+            .map(|addr| {
+                let mut runner = program.runner();
+                runner.push_input(W::from_isize(addr as isize));
+                runner
+            })
+            .collect();
+
+        Network::new(machines)
+    }
+
+    pub fn machines(&self) -> &[IntcodeRunner<W>] {
+        &self.machines
+    }
+
+    /// queues a packet for its destination. packets to addresses outside the network are dropped.
+    pub fn send(&mut self, packet: Packet<W>) {
+        if let Some(addr) = self.addr_of(packet.dest) {
+            self.queues[addr].push_back((packet.x, packet.y));
+            self.polls[addr] = 0;
+        }
+    }
+
+    fn addr_of(&self, dest: W) -> Option<usize> {
+        dest.to_isize()
+            .filter(|&addr| addr >= 0 && (addr as usize) < self.machines.len())
+            .map(|addr| addr as usize)
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+            && self
+                .machines
+                .iter()
+                .zip(&self.polls)
+                .all(|(machine, &polls)| machine.has_halted() || polls >= IDLE_POLLS)
+    }
+
+    /// runs every machine in turn until the network halts, goes idle, is stopped by `monitor`, or
+    /// a machine pauses on a watchpoint or reaches a limit.
+    pub fn run<M>(&mut self, monitor: &mut M) -> Result<NetState<W>, NetworkError<W>>
+    where
+        M: Monitor<W> + ?Sized,
+    {
+        loop {
+            if self.machines.iter().all(IntcodeRunner::has_halted) {
+                return Ok(NetState::Halted);
+            }
+
+            if self.is_idle() {
+                match monitor.notify(Event::Idle) {
+                    Action::Continue => return Ok(NetState::Idle),
+                    Action::Send(packet) => self.send(packet),
+                    Action::Stop => return Ok(NetState::Stopped),
+                }
+            }
+
+            for addr in 0..self.machines.len() {
+                let mut packets = Vec::new();
+                let stop = self.run_machine(addr, &mut packets)?;
+
+                for packet in packets {
+                    if self.addr_of(packet.dest).is_some() {
+                        self.send(packet);
+                        continue;
+                    }
+
+                    match monitor.notify(Event::Unrouted(packet)) {
+                        Action::Continue => {}
+                        Action::Send(packet) => self.send(packet),
+                        Action::Stop => return Ok(NetState::Stopped),
+                    }
+                }
+
+                if let Some(stop) = stop {
+                    return Ok(stop);
+                }
+            }
+        }
+    }
+
+    /// runs a machine until it asks for input, collecting the packets it sent meanwhile.
+    /// returns the state to stop the network with, if the machine paused or reached a limit.
+    fn run_machine(&mut self, addr: usize, packets: &mut Vec<Packet<W>>) -> Result<Option<NetState<W>>, NetworkError<W>> {
+        let machine = &mut self.machines[addr];
+
+        while !machine.has_halted() {
+            let run_state = machine
+                .run()
+                .map_err(|error| NetworkError::Fault { machine: addr, error })?;
+
+            match run_state {
+                RunState::Output(word) => {
+                    self.polls[addr] = 0;
+                    self.sending[addr].push(word);
+
+                    if let [dest, x, y] = self.sending[addr][..] {
+                        packets.push(Packet { dest, x, y });
+                        self.sending[addr].clear();
+                    }
+                }
+
+                RunState::NeedsInput => {
+                    //inputs are consumed from the back, so Y goes in first
+                    match self.queues[addr].pop_front() {
+                        Some((x, y)) => {
+                            self.polls[addr] = 0;
+                            machine.push_input(y);
+                            machine.push_input(x);
+                        }
+                        None => {
+                            self.polls[addr] += 1;
+                            machine.push_input(W::from_isize(-1));
+                        }
+                    }
+
+                    break;
+                }
+
+                RunState::LimitReached(limit) => {
+                    return Ok(Some(NetState::LimitReached { machine: addr, limit }));
+                }
+
+                RunState::Watchpoint(hit) => return Ok(Some(NetState::Watchpoint { machine: addr, hit })),
+
+                RunState::Halted if !self.sending[addr].is_empty() => {
+                    let words = mem::take(&mut self.sending[addr]);
+                    return Err(NetworkError::TruncatedPacket { machine: addr, words });
+                }
+
+                RunState::Halted => break,
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// the NAT of 2019's day 23: keeps the last packet sent outside the network, and sends it to
+/// machine 0 whenever the network is idle. stops the network instead of sending the same Y twice
+/// in a row.
+pub struct Nat<W = isize> {
+    last: Option<Packet<W>>,
+    sent: Vec<Packet<W>>,
+}
+
+impl<W: Word> Nat<W> {
+    pub fn new() -> Nat<W> {
+        Nat {
+            last: None,
+            sent: Vec::new(),
+        }
+    }
+
+    /// the last packet received.
+    pub fn last(&self) -> Option<Packet<W>> {
+        self.last
+    }
+
+    /// every packet sent to machine 0, oldest first.
+    pub fn sent(&self) -> &[Packet<W>] {
+        &self.sent
+    }
+}
+
+impl<W: Word> Default for Nat<W> {
+    fn default() -> Self {
+        Nat::new()
+    }
+}
+
+impl<W: Word> Monitor<W> for Nat<W> {
+    fn notify(&mut self, event: Event<W>) -> Action<W> {
+        match event {
+            Event::Unrouted(packet) => {
+                self.last = Some(packet);
+                Action::Continue
+            }
+
+            Event::Idle => {
+                let packet = match self.last {
+                    Some(packet) => Packet {
+                        dest: W::default(),
+                        ..packet
+                    },
+                    None => return Action::Continue,
+                };
+
+                if self.sent.last().is_some_and(|prev| prev.y == packet.y) {
+                    return Action::Stop;
+                }
+
+                self.sent.push(packet);
+                Action::Send(packet)
+            }
+        }
+    }
+}
+
+/// machine 0 starts by sending (1, 7, 0). every machine passes the packets it receives on to the
+/// next address, so the last one sends them outside the network.
+#[cfg(test)]
+fn relay(size: usize) -> Network {
+    let src = "
+            in addr
+            jt [addr], @recv
+            out 1
+            out 7
+            out 0
+    recv:   in x
+            eq [x], -1, tmp
+            jt [tmp], @recv
+            in y
+            add [addr], 1, tmp
+            out [tmp]
+            out [x]
+            out [y]
+            jt 1, @recv
+    addr:   data 0
+    x:      data 0
+    y:      data 0
+    tmp:    data 0
+    ";
+    let mem = crate::intcode::assembler::assemble(src).unwrap();
+    Network::boot(&Program::new(&mem), size)
+}
+
+#[test]
+fn reports_unrouted_packets() {
+    let mut network = relay(3);
+    let mut unrouted = Vec::new();
+
+    let state = network.run(&mut |event| {
+        if let Event::Unrouted(packet) = event {
+            unrouted.push(packet);
+        }
+        Action::Continue
+    });
+
+    assert_eq!(state, Ok(NetState::Idle));
+    assert_eq!(unrouted, vec![Packet { dest: 3, x: 7, y: 0 }]);
+}
+
+#[test]
+fn nat_wakes_idle_network() {
+    let mut network = relay(3);
+    let mut nat = Nat::new();
+
+    assert_eq!(network.run(&mut nat), Ok(NetState::Stopped));
+    assert_eq!(nat.sent(), &[Packet { dest: 0, x: 7, y: 0 }]);
+}
+
+#[test]
+fn stops_on_watchpoints_and_truncated_packets() {
+    use crate::intcode::{WatchAction, Watchpoint};
+
+    //polls its input forever, storing it at 5
+    let mut poller = IntcodeRunner::new(&[3, 5, 1105, 1, 0, 0]);
+    poller.add_watchpoint(Watchpoint::writes(5..6, WatchAction::Pause));
+
+    let mut network = Network::new(vec![poller]);
+    let state = network.run(&mut |_| Action::Continue);
+    assert!(matches!(state, Ok(NetState::Watchpoint { machine: 0, hit }) if hit.value == -1));
+
+    //sends a destination, then halts
+    let mut network = Network::new(vec![IntcodeRunner::new(&[104, 5, 99])]);
+    assert_eq!(
+        network.run(&mut |_| Action::Continue),
+        Err(NetworkError::TruncatedPacket { machine: 0, words: vec![5] })
+    );
+}
@@ -0,0 +1,137 @@
+//! programs decoded ahead of time, for spawning many runners of the same program.
+//!
+//! a Program decodes every instruction in its memory image once. runners spawned from it share
+//! both the image (see memory.rs) and the decoded instructions, so a fresh runner costs next to
+//! nothing and never decodes an instruction the program didn't modify.
+//!
+//! since Intcode can modify itself, each runner tracks which cached instructions it wrote over,
+//! and decodes those from memory as usual. the set is only allocated once the program writes to
+//! its own image.
+
+use crate::intcode::memory::Memory;
+use crate::intcode::{IntcodeRunner, Opcode, Word};
+use std::sync::Arc;
+
+const BITS: usize = u64::BITS as usize;
+
+/// the most words an instruction can span.
+const MAX_INSTRUCTION_LEN: usize = 4;
+
+#[derive(Clone)]
+pub struct Program<W = isize> {
+    pub(super) mem: Memory<W>,
+    /// the instruction at every address, if one can be decoded there.
+    pub(super) decoded: Arc<Vec<Option<Opcode<W>>>>,
+}
+
+impl Program {
+    pub fn new(mem: &[isize]) -> Program {
+        Program::from_words(mem)
+    }
+}
+
+impl<W: Word> Program<W> {
+    /// like new(), for any word type.
+    pub fn from_words(mem: &[W]) -> Program<W> {
+        let runner = IntcodeRunner::from_words(mem);
+
+        //instructions that extend past the end of the image aren't cached, since writes beyond it
+        //aren't tracked
+        let decoded = (0..mem.len() as isize)
+            .map(|addr| {
+                runner
+                    .decode_at(addr)
+                    .ok()
+                    .filter(|opcode| addr + (opcode.params().len() as isize) < mem.len() as isize)
+            })
+            .collect();
+
+        Program {
+            mem: runner.mem,
+            decoded: Arc::new(decoded),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mem.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mem.is_empty()
+    }
+
+    pub fn words(&self) -> Vec<W> {
+        self.mem.to_vec()
+    }
+
+    /// a fresh runner, as if created by IntcodeRunner::from_words from this program's image.
+    pub fn runner(&self) -> IntcodeRunner<W> {
+        let mut runner = IntcodeRunner::from_words(&[]);
+        runner.mem = self.mem.clone();
+        runner.decoded = Some(Arc::clone(&self.decoded));
+        runner
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// the pre-decoded instruction at `ptr`, unless it's unknown or has been written over.
+    pub(super) fn cached_opcode(&self, ptr: isize) -> Option<Opcode<W>> {
+        let decoded = self.decoded.as_ref()?;
+        if ptr < 0 || self.is_code_dirty(ptr as usize) {
+            return None;
+        }
+
+        decoded.get(ptr as usize)?.clone()
+    }
+
+    /// whether the instruction at `ptr` may have been written over.
+    pub(super) fn is_code_dirty(&self, ptr: usize) -> bool {
+        self.code_dirty
+            .get(ptr / BITS)
+            .is_some_and(|bits| bits & (1 << (ptr % BITS)) != 0)
+    }
+
+    /// marks every cached instruction that covers `addr` as stale.
+    pub(super) fn invalidate_code(&mut self, addr: usize) {
+        let len = match &self.decoded {
+            Some(decoded) => decoded.len(),
+            None => return,
+        };
+
+        if addr >= len {
+            return;
+        }
+
+        if self.code_dirty.is_empty() {
+            self.code_dirty = vec![0; len.div_ceil(BITS)];
+        }
+
+        for ptr in addr.saturating_sub(MAX_INSTRUCTION_LEN - 1)..=addr {
+            self.code_dirty[ptr / BITS] |= 1 << (ptr % BITS);
+        }
+    }
+}
+
+#[test]
+fn runners_share_decoded_instructions() {
+    let mem = vec![1, 0, 0, 0, 99];
+    let program = Program::new(&mem);
+
+    let mut runner = program.runner();
+    assert_eq!(runner.cached_opcode(4), Some(Opcode::Halt));
+
+    //overwrites its first instruction with 2, i.e. Mul
+    runner.run().unwrap();
+    assert_eq!(runner.value_at_pos_0(), 2);
+    assert_eq!(runner.cached_opcode(0), None);
+
+    assert!(program.runner().cached_opcode(0).is_some());
+}
+
+#[test]
+fn self_modifying_code_is_decoded_again() {
+    let mut runner = Program::new(crate::intcode::fixtures::OVERWRITES_OUT).runner();
+
+    assert_eq!(runner.outputs().collect::<Result<Vec<_>, _>>(), Ok(vec![]));
+    assert!(runner.has_halted());
+}
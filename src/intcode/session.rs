@@ -0,0 +1,259 @@
+//! recording the inputs and outputs of a run, and replaying them to check a run does the same.
+//!
+//! a Session holds every input the program consumed and every output it produced, in order, along
+//! with the instruction that consumed or produced it. sessions are saved as plain text:
+//!
+//! ```text
+//! intcode session 1
+//! in 0 5
+//! out 4 10
+//! ```
+//!
+//! replaying a session runs a fresh runner, feeding it the recorded inputs whenever it asks for
+//! input, and stops at the first event that doesn't match the recording.
+
+use crate::intcode::{IntcodeError, IntcodeRunner, RunState, Word};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+const HEADER: &str = "intcode session";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Event<W = isize> {
+    /// an input consumed by the In instruction at `inst_ptr`.
+    Input { inst_ptr: isize, value: W },
+    /// an output produced by the Out instruction at `inst_ptr`.
+    Output { inst_ptr: isize, value: W },
+}
+
+impl<W: Word> fmt::Display for Event<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::Input { inst_ptr, value } => write!(f, "in {} {}", inst_ptr, value),
+            Event::Output { inst_ptr, value } => write!(f, "out {} {}", inst_ptr, value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Session<W = isize> {
+    events: Vec<Event<W>>,
+}
+
+/// where a replay first differed from its session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Divergence<W = isize> {
+    /// the index of the first event that doesn't match.
+    pub index: usize,
+    /// the instruction pointer of the replaying runner at that point.
+    pub inst_ptr: isize,
+    /// the recorded event, or None if the replay went past the end of the session.
+    pub expected: Option<Event<W>>,
+    /// what the replay did instead, or None if it halted or asked for input the session didn't
+    /// record.
+    pub found: Option<Event<W>>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplayError<W = isize> {
+    Fault(IntcodeError<W>),
+    Diverged(Divergence<W>),
+}
+
+impl<W: Word> fmt::Display for ReplayError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let div = match self {
+            ReplayError::Fault(e) => return write!(f, "replay faulted: {}", e),
+            ReplayError::Diverged(div) => div,
+        };
+
+        write!(f, "replay diverged at event {} (instruction pointer {}): ", div.index, div.inst_ptr)?;
+        match (div.expected, div.found) {
+            (Some(expected), Some(found)) => write!(f, "expected `{}`, found `{}`", expected, found),
+            (Some(expected), None) => write!(f, "expected `{}`, but the program stopped", expected),
+            (None, Some(found)) => write!(f, "found `{}` past the end of the session", found),
+            (None, None) => write!(f, "the program stopped"),
+        }
+    }
+}
+
+impl<W: Word> Error for ReplayError<W> {}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl<W: Word> Session<W> {
+    pub fn new() -> Session<W> {
+        Session { events: Vec::new() }
+    }
+
+    pub fn events(&self) -> &[Event<W>] {
+        &self.events
+    }
+
+    /// the recorded inputs, in the order they were consumed.
+    pub fn inputs(&self) -> Vec<W> {
+        self.events
+            .iter()
+            .filter_map(|event| match *event {
+                Event::Input { value, .. } => Some(value),
+                Event::Output { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{} {}", HEADER, VERSION)?;
+        for event in &self.events {
+            writeln!(out, "{}", event)?;
+        }
+
+        out.flush()
+    }
+
+    pub fn load(input: impl BufRead) -> io::Result<Session<W>> {
+        let mut lines = input.lines();
+
+        let header = lines.next().ok_or_else(|| invalid_data("empty session".to_string()))??;
+        if header != format!("{} {}", HEADER, VERSION) {
+            return Err(invalid_data(format!("not a version {} Intcode session", VERSION)));
+        }
+
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            let malformed = || invalid_data(format!("malformed event `{}`", line));
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (kind, inst_ptr, value) = match parts[..] {
+                [kind, inst_ptr, value] => (kind, inst_ptr, value),
+                [] => continue,
+                _ => return Err(malformed()),
+            };
+
+            let inst_ptr = inst_ptr.parse().map_err(|_| malformed())?;
+            let value = value.parse().map_err(|_| malformed())?;
+
+            events.push(match kind {
+                "in" => Event::Input { inst_ptr, value },
+                "out" => Event::Output { inst_ptr, value },
+                _ => return Err(malformed()),
+            });
+        }
+
+        Ok(Session { events })
+    }
+
+    /// runs `runner` until it halts, feeding it the recorded inputs and checking that it consumes
+    /// and produces exactly the recorded events. the runner should be in the state the recorded one
+    /// started in, with no inputs queued. returns NeedsInput if the program asks for more input
+    /// than was recorded, or LimitReached if the runner reaches a limit first.
+    pub fn replay(&self, runner: &mut IntcodeRunner<W>) -> Result<RunState<W>, ReplayError<W>> {
+        //the replay is checked against a recording of its own
+        let previous = runner.session.replace(Session::new());
+        let res = self.check_replay(runner);
+        runner.session = previous;
+        res
+    }
+
+    fn check_replay(&self, runner: &mut IntcodeRunner<W>) -> Result<RunState<W>, ReplayError<W>> {
+        let mut index = 0;
+
+        let diverged = |index, runner: &IntcodeRunner<W>, found| {
+            Err(ReplayError::Diverged(Divergence {
+                index,
+                inst_ptr: runner.inst_ptr(),
+                expected: self.events.get(index).copied(),
+                found,
+            }))
+        };
+
+        loop {
+            let run_state = runner.run().map_err(ReplayError::Fault)?;
+
+            let recorded = match &runner.session {
+                Some(session) => session.events[index..].to_vec(),
+                None => Vec::new(),
+            };
+
+            for found in recorded {
+                if self.events.get(index) != Some(&found) {
+                    return diverged(index, runner, Some(found));
+                }
+                index += 1;
+            }
+
+            match (run_state, self.events.get(index)) {
+                (RunState::NeedsInput, Some(&Event::Input { value, .. })) => runner.push_input(value),
+                (RunState::NeedsInput, None) => return Ok(RunState::NeedsInput),
+                (RunState::LimitReached(limit), _) => return Ok(RunState::LimitReached(limit)),
+                (RunState::Halted, None) => return Ok(RunState::Halted),
+                (RunState::NeedsInput, _) | (RunState::Halted, _) => return diverged(index, runner, None),
+                (RunState::Output(_), _) | (RunState::Watchpoint(_), _) => {}
+            }
+        }
+    }
+}
+
+impl<W: Word> Default for Session<W> {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// starts recording inputs and outputs to a new session, replacing any session being recorded.
+    pub fn start_recording(&mut self) {
+        self.session = Some(Session::new());
+    }
+
+    /// stops recording, and returns the recorded session.
+    pub fn stop_recording(&mut self) -> Option<Session<W>> {
+        self.session.take()
+    }
+
+    pub fn session(&self) -> Option<&Session<W>> {
+        self.session.as_ref()
+    }
+
+    pub(super) fn record(&mut self, event: Event<W>) {
+        if let Some(session) = &mut self.session {
+            session.events.push(event);
+        }
+    }
+}
+
+#[test]
+fn replays_recorded_session() {
+    let mem = crate::intcode::fixtures::doubler();
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.start_recording();
+    for input in &[3, 5, 0] {
+        runner.push_input(*input);
+        while let Ok(RunState::Output(_)) = runner.run() {}
+    }
+
+    let session = runner.stop_recording().unwrap();
+    assert_eq!(session.inputs(), vec![3, 5, 0]);
+
+    let mut saved = Vec::new();
+    session.save(&mut saved).unwrap();
+    assert!(String::from_utf8_lossy(&saved).starts_with("intcode session 1\nin 0 3\nout 9 6\n"));
+
+    let loaded = Session::load(&saved[..]).unwrap();
+    assert_eq!(loaded.replay(&mut IntcodeRunner::new(&mem)), Ok(RunState::Halted));
+
+    //a program that triples instead
+    let mut tripler = mem.clone();
+    tripler[7] = 3;
+    let div = match loaded.replay(&mut IntcodeRunner::new(&tripler)) {
+        Err(ReplayError::Diverged(div)) => div,
+        other => panic!("expected a divergence, got {:?}", other),
+    };
+
+    assert_eq!(div.index, 1);
+    assert_eq!(div.found, Some(Event::Output { inst_ptr: 9, value: 9 }));
+}
@@ -0,0 +1,197 @@
+//! saving and restoring the whole state of a machine.
+//!
+//! snapshots are plain text, one field per line, so they can be read and diffed:
+//!
+//! ```text
+//! intcode snapshot 1
+//! inst_ptr 4
+//! offset 0
+//! halted false
+//! input_mode consume
+//! output 7
+//! inputs 1 2
+//! mem 3 9 4 9 99 0 0 0 0 7
+//! extra_mem 1000=5 1024=-3
+//! ```
+//!
+//! inputs are listed in queue order, so the last one is consumed first.
+//! `mem` holds the program's words, and `extra_mem` the non-zero words past its end.
+//! watchpoints and traces are debugging aids rather than machine state, so they aren't saved.
+//! neither is the runner's InstructionSet, which can't be written down: a snapshot has to be loaded
+//! with the set it was saved with, e.g. one including reboot() for day13.
+
+use crate::intcode::{InputMode, InstructionSet, IntcodeRunner, Word};
+use std::io::{self, BufRead, Write};
+
+const HEADER: &str = "intcode snapshot";
+const VERSION: u32 = 1;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn join<W: Word>(vals: &[W]) -> String {
+    let vals: Vec<String> = vals.iter().map(|val| val.to_string()).collect();
+    vals.join(" ")
+}
+
+/// reads the next line, which has to hold the field `name`, and returns its value.
+fn field(lines: &mut impl Iterator<Item = io::Result<String>>, name: &str) -> io::Result<String> {
+    let line = lines
+        .next()
+        .ok_or_else(|| invalid_data(format!("missing field `{}`", name)))??;
+
+    let mut parts = line.splitn(2, ' ');
+    if parts.next() != Some(name) {
+        return Err(invalid_data(format!("expected field `{}`, found `{}`", name, line)));
+    }
+
+    Ok(parts.next().unwrap_or_default().trim().to_string())
+}
+
+fn parse<T: std::str::FromStr>(name: &str, s: &str) -> io::Result<T> {
+    s.parse()
+        .map_err(|_| invalid_data(format!("malformed value `{}` for field `{}`", s, name)))
+}
+
+fn parse_list<W: Word>(name: &str, s: &str) -> io::Result<Vec<W>> {
+    s.split_whitespace().map(|val| parse(name, val)).collect()
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    pub fn save_snapshot(&self, mut out: impl Write) -> io::Result<()> {
+        let input_mode = match self.input_mode {
+            InputMode::ConsumeInput => "consume",
+            InputMode::SingleInput => "single",
+        };
+
+        let output = match self.output {
+            Some(output) => output.to_string(),
+            None => "none".to_string(),
+        };
+
+        let inputs: Vec<W> = self.inputs.iter().copied().collect();
+
+        let extra_mem: Vec<String> = self
+            .mem
+            .extra_words()
+            .into_iter()
+            .map(|(addr, val)| format!("{}={}", addr, val))
+            .collect();
+
+        writeln!(out, "{} {}", HEADER, VERSION)?;
+        writeln!(out, "inst_ptr {}", self.inst_ptr)?;
+        writeln!(out, "offset {}", self.offset)?;
+        writeln!(out, "halted {}", self.halted)?;
+        writeln!(out, "input_mode {}", input_mode)?;
+        writeln!(out, "output {}", output)?;
+        writeln!(out, "inputs {}", join(&inputs))?;
+        writeln!(out, "mem {}", join(&self.mem.to_vec()))?;
+        writeln!(out, "extra_mem {}", extra_mem.join(" "))?;
+
+        out.flush()
+    }
+
+    /// restores a saved runner, which runs `instructions`.
+    pub fn load_snapshot(input: impl BufRead, instructions: &InstructionSet<W>) -> io::Result<IntcodeRunner<W>> {
+        let mut lines = input.lines();
+
+        let header = lines.next().ok_or_else(|| invalid_data("empty snapshot".to_string()))??;
+        let version = header
+            .strip_prefix(HEADER)
+            .ok_or_else(|| invalid_data("not an Intcode snapshot".to_string()))?;
+        let version: u32 = parse("version", version.trim())?;
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported snapshot version {}", version)));
+        }
+
+        let inst_ptr = parse("inst_ptr", &field(&mut lines, "inst_ptr")?)?;
+        let offset = parse("offset", &field(&mut lines, "offset")?)?;
+        let halted = parse("halted", &field(&mut lines, "halted")?)?;
+
+        let input_mode = match &field(&mut lines, "input_mode")?[..] {
+            "consume" => InputMode::ConsumeInput,
+            "single" => InputMode::SingleInput,
+            other => return Err(invalid_data(format!("unknown input mode `{}`", other))),
+        };
+
+        let output = match &field(&mut lines, "output")?[..] {
+            "none" => None,
+            output => Some(parse("output", output)?),
+        };
+
+        let inputs = parse_list("inputs", &field(&mut lines, "inputs")?)?;
+        let mem = parse_list("mem", &field(&mut lines, "mem")?)?;
+
+        let mut extra_mem = Vec::new();
+        for entry in field(&mut lines, "extra_mem")?.split_whitespace() {
+            let (addr, val) = entry
+                .split_once('=')
+                .ok_or_else(|| invalid_data(format!("malformed extra_mem entry `{}`", entry)))?;
+            extra_mem.push((parse::<usize>("extra_mem", addr)?, parse("extra_mem", val)?));
+        }
+
+        let mut runner = IntcodeRunner::from_words(&mem);
+        runner.set_instructions(instructions.clone());
+        for (addr, val) in extra_mem {
+            runner.write(addr, val);
+        }
+
+        runner.inst_ptr = inst_ptr;
+        runner.offset = offset;
+        runner.inputs = inputs.into_iter().collect();
+        runner.input_mode = input_mode;
+        runner.output = output;
+        runner.halted = halted;
+
+        Ok(runner)
+    }
+}
+
+#[test]
+fn resumes_from_snapshot() {
+    //adds up two inputs, with the relative base moved past the end of the program
+    let mem = vec![109, 1000, 203, 0, 203, 1, 22201, 0, 1, 0, 204, 0, 99];
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.push_input(2);
+    assert_eq!(runner.run(), Ok(crate::intcode::RunState::NeedsInput));
+
+    let mut saved = Vec::new();
+    runner.save_snapshot(&mut saved).unwrap();
+    assert!(String::from_utf8_lossy(&saved).contains("\nextra_mem 1000=2\n"));
+
+    let mut restored: IntcodeRunner =
+        IntcodeRunner::load_snapshot(&saved[..], &InstructionSet::standard()).unwrap();
+    restored.push_input(5);
+    assert_eq!(restored.outputs().collect::<Result<Vec<_>, _>>(), Ok(vec![7]));
+}
+
+#[test]
+fn rejects_other_versions() {
+    let snapshot = "intcode snapshot 2\ninst_ptr 0\n";
+    match IntcodeRunner::<isize>::load_snapshot(snapshot.as_bytes(), &InstructionSet::standard()) {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        Ok(_) => panic!("loaded a snapshot with an unknown version"),
+    }
+}
+
+#[test]
+fn resumes_with_extensions() {
+    use crate::intcode::extensions::reboot;
+    use crate::intcode::RunState;
+
+    let mut instructions = InstructionSet::standard();
+    instructions.register(reboot());
+
+    //outputs 1, then reboots
+    let mut runner = IntcodeRunner::new(&[104, 1, 0]);
+    runner.set_instructions(instructions.clone());
+    assert_eq!(runner.run(), Ok(RunState::Output(1)));
+
+    let mut saved = Vec::new();
+    runner.save_snapshot(&mut saved).unwrap();
+
+    let mut restored = IntcodeRunner::load_snapshot(&saved[..], &instructions).unwrap();
+    assert_eq!(restored.run(), Ok(RunState::Output(1)));
+    assert_eq!(restored.inst_ptr(), 2);
+}
@@ -0,0 +1,320 @@
+//! runs machines on their own threads, connected by channels.
+//!
+//! a Cluster hands out channels, made of a Link to send words and an Inbox to receive them, and
+//! spawns machines that read from an Inbox and write to a Link. a machine blocks while it waits
+//! for input, and its thread ends once it halts, or once every Link to its Inbox is gone. either
+//! way its own Link is dropped, so machines reading from it shut down in turn.
+//!
+//! words in flight are counted, so Cluster::join can tell when the machines have deadlocked:
+//! every machine still running is waiting for input, nothing is on its way to them, and nobody
+//! outside the cluster holds a Link that could still send them something. deadlocked machines are
+//! shut down as if their inputs had closed.
+
+use crate::intcode::{InputSource, IntcodeError, IntcodeRunner, OutputSink, RunState, Word};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// how often a waiting machine checks whether it's being shut down.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Default)]
+struct State {
+    /// machines that haven't finished yet.
+    running: usize,
+    /// machines blocked waiting for input.
+    waiting: usize,
+    /// Links not owned by a machine.
+    free_links: usize,
+    /// words sent but not yet received, by inbox.
+    pending: Vec<usize>,
+    /// whether each inbox belongs to a machine.
+    owned: Vec<bool>,
+    deadlocked: bool,
+}
+
+impl State {
+    fn is_deadlocked(&self) -> bool {
+        self.running > 0
+            && self.waiting == self.running
+            && self.free_links == 0
+            && self.pending.iter().zip(&self.owned).all(|(&pending, &owned)| !owned || pending == 0)
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// the sending end of a channel.
+pub struct Link<W> {
+    sender: Sender<W>,
+    inbox: usize,
+    owned: bool,
+    shared: Arc<Shared>,
+}
+
+impl<W> Link<W> {
+    /// sends a word, which is dropped if the receiving end is gone.
+    pub fn send(&self, word: W) {
+        self.shared.lock().pending[self.inbox] += 1;
+
+        if self.sender.send(word).is_err() {
+            self.shared.lock().pending[self.inbox] -= 1;
+        }
+    }
+}
+
+impl<W> Clone for Link<W> {
+    fn clone(&self) -> Self {
+        self.shared.lock().free_links += 1;
+
+        Link {
+            sender: self.sender.clone(),
+            inbox: self.inbox,
+            owned: false,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<W> Drop for Link<W> {
+    fn drop(&mut self) {
+        if !self.owned {
+            self.shared.lock().free_links -= 1;
+            self.shared.changed.notify_all();
+        }
+    }
+}
+
+impl<W> OutputSink<W> for Link<W> {
+    fn send(&mut self, output: W) {
+        Link::send(self, output)
+    }
+}
+
+/// the receiving end of a channel.
+pub struct Inbox<W> {
+    receiver: Receiver<W>,
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl<W> Inbox<W> {
+    /// blocks until a word arrives, or returns None once every Link to the inbox is gone.
+    pub fn recv(&self) -> Option<W> {
+        let word = self.receiver.recv().ok()?;
+        self.shared.lock().pending[self.id] -= 1;
+        Some(word)
+    }
+
+    /// the words that have already arrived, without blocking.
+    pub fn drain(&self) -> Vec<W> {
+        let words: Vec<W> = self.receiver.try_iter().collect();
+        self.shared.lock().pending[self.id] -= words.len();
+        words
+    }
+}
+
+/// a machine's inbox, which gives up waiting once the machines deadlock.
+struct MachineInput<W>(Inbox<W>);
+
+impl<W> InputSource<W> for MachineInput<W> {
+    fn next_input(&mut self) -> Option<W> {
+        let inbox = &self.0;
+        inbox.shared.lock().waiting += 1;
+        inbox.shared.changed.notify_all();
+
+        loop {
+            let res = inbox.receiver.recv_timeout(POLL_INTERVAL);
+
+            let mut state = inbox.shared.lock();
+            match res {
+                Ok(word) => {
+                    state.waiting -= 1;
+                    state.pending[inbox.id] -= 1;
+                    return Some(word);
+                }
+                Err(RecvTimeoutError::Timeout) if !state.deadlocked => continue,
+                Err(_) => {
+                    state.waiting -= 1;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// a machine whose thread has ended, along with the reason it stopped: Halted, or NeedsInput if
+/// its inputs closed or it deadlocked.
+pub struct Finished<W> {
+    pub runner: IntcodeRunner<W>,
+    pub result: Result<RunState<W>, IntcodeError<W>>,
+}
+
+/// returned by Cluster::join if the machines deadlocked, holding every machine.
+pub struct Deadlock<W>(pub Vec<Finished<W>>);
+
+pub struct Cluster<W = isize> {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<Finished<W>>>,
+}
+
+impl<W: Word> Cluster<W> {
+    pub fn new() -> Cluster<W> {
+        Cluster {
+            shared: Arc::default(),
+            threads: Vec::new(),
+        }
+    }
+
+    pub fn channel(&self) -> (Link<W>, Inbox<W>) {
+        let (sender, receiver) = mpsc::channel();
+
+        let mut state = self.shared.lock();
+        let id = state.pending.len();
+        state.pending.push(0);
+        state.owned.push(false);
+        state.free_links += 1;
+
+        let link = Link {
+            sender,
+            inbox: id,
+            owned: false,
+            shared: Arc::clone(&self.shared),
+        };
+
+        let inbox = Inbox {
+            receiver,
+            id,
+            shared: Arc::clone(&self.shared),
+        };
+
+        (link, inbox)
+    }
+
+    /// runs `runner` on its own thread, taking inputs from `input` once its own inputs run out
+    /// and sending its outputs to `output`.
+    pub fn spawn(&mut self, mut runner: IntcodeRunner<W>, input: Inbox<W>, mut output: Link<W>) {
+        {
+            let mut state = self.shared.lock();
+            state.running += 1;
+            state.owned[input.id] = true;
+            if !output.owned {
+                output.owned = true;
+                state.free_links -= 1;
+            }
+        }
+
+        let shared = Arc::clone(&self.shared);
+        let mut input = MachineInput(input);
+
+        self.threads.push(thread::spawn(move || {
+            let result = runner.run_with(&mut input, &mut output);
+
+            //dropping the link before reporting lets the machines reading from it finish too
+            drop(output);
+            shared.lock().running -= 1;
+            shared.changed.notify_all();
+
+            Finished { runner, result }
+        }));
+    }
+
+    /// waits for every machine to finish, in the order they were spawned.
+    pub fn join(self) -> Result<Vec<Finished<W>>, Deadlock<W>> {
+        let mut state = self.shared.lock();
+        while state.running > 0 && !state.is_deadlocked() {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+
+        let deadlocked = state.running > 0;
+        state.deadlocked = deadlocked;
+        drop(state);
+
+        let machines = self
+            .threads
+            .into_iter()
+            .map(|thread| thread.join().expect("Intcode thread panicked"))
+            .collect();
+
+        if deadlocked {
+            Err(Deadlock(machines))
+        } else {
+            Ok(machines)
+        }
+    }
+}
+
+impl<W: Word> Default for Cluster<W> {
+    fn default() -> Self {
+        Cluster::new()
+    }
+}
+
+/// a ring of machines that each pass on their input plus 1, and halt once they've sent 10 or more.
+#[cfg(test)]
+fn ring(size: usize) -> (Cluster, Link<isize>) {
+    let src = "
+    loop:   in x
+            add [x], 1, x
+            out [x]
+            lt [x], 10, tmp
+            jt [tmp], @loop
+            halt
+    x:      data 0
+    tmp:    data 0
+    ";
+    let mem = crate::intcode::assembler::assemble(src).unwrap();
+
+    let mut cluster = Cluster::new();
+    let (mut links, inboxes): (Vec<_>, Vec<_>) = (0..size).map(|_| cluster.channel()).unzip();
+    let first = links[0].clone();
+
+    //each machine sends to the next one's inbox
+    links.rotate_left(1);
+    for (inbox, link) in inboxes.into_iter().zip(links) {
+        cluster.spawn(IntcodeRunner::new(&mem), inbox, link);
+    }
+
+    (cluster, first)
+}
+
+#[test]
+fn machines_halt_in_turn() {
+    let (cluster, first) = ring(5);
+    first.send(0);
+    drop(first);
+
+    let machines = match cluster.join() {
+        Ok(machines) => machines,
+        Err(_) => panic!("the ring deadlocked"),
+    };
+
+    assert!(machines.iter().all(|machine| machine.result == Ok(RunState::Halted)));
+    let last_outputs: Vec<_> = machines.iter().map(|machine| machine.runner.output()).collect();
+    assert_eq!(last_outputs, vec![Some(11), Some(12), Some(13), Some(14), Some(10)]);
+}
+
+#[test]
+fn detects_deadlocks() {
+    //nobody sends the first word
+    let (cluster, first) = ring(3);
+    drop(first);
+
+    match cluster.join() {
+        Err(Deadlock(machines)) => {
+            assert!(machines.iter().all(|machine| machine.result == Ok(RunState::NeedsInput)))
+        }
+        Ok(_) => panic!("the ring didn't deadlock"),
+    }
+}
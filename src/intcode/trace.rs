@@ -0,0 +1,354 @@
+//! instruction-level execution traces.
+//!
+//! once tracing is started, every instruction that completes is recorded along with the operand
+//! values it read and the memory it wrote. instructions that fault or wait for input aren't
+//! recorded, since they'll be executed again.
+//!
+//! traces can be saved in a compact binary format: the magic bytes `ICTR` and a version byte,
+//! followed by one record after another until the end of the file. a record is a sequence of
+//! zigzag-encoded LEB128 varints:
+//!
+//! ```text
+//! inst_ptr, #words, instruction words..., #reads, reads..., #writes, (addr, value)...
+//! ```
+//!
+//! the instruction words are decoded again on loading, so a trace has to be loaded with the
+//! InstructionSet it was recorded with, e.g. one including reboot() for day13.
+
+use crate::intcode::{InstructionSet, IntcodeRunner, Opcode, Word};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceRecord<W = isize> {
+    pub inst_ptr: isize,
+    pub opcode: Opcode<W>,
+    /// the values of the operands the instruction read, in order.
+    pub reads: Vec<W>,
+    /// (address, new value) for every word the instruction wrote.
+    pub writes: Vec<(isize, W)>,
+}
+
+impl<W: Word> fmt::Display for TraceRecord<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut effects: Vec<String> = Vec::new();
+
+        if !self.reads.is_empty() {
+            let reads: Vec<String> = self.reads.iter().map(|val| val.to_string()).collect();
+            effects.push(format!("read {}", reads.join(", ")));
+        }
+
+        for (addr, val) in &self.writes {
+            effects.push(format!("[{}] <- {}", addr, val));
+        }
+
+        if effects.is_empty() {
+            write!(f, "{:>6}: {}", self.inst_ptr, self.opcode)
+        } else {
+            write!(f, "{:>6}: {:<32} ; {}", self.inst_ptr, self.opcode.to_string(), effects.join("; "))
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Trace<W = isize> {
+    records: Vec<TraceRecord<W>>,
+    /// the record of the instruction being executed.
+    pending: Option<TraceRecord<W>>,
+}
+
+impl<W: Word> Default for Trace<W> {
+    fn default() -> Self {
+        Trace {
+            records: Vec::new(),
+            pending: None,
+        }
+    }
+}
+
+impl<W: Word> Trace<W> {
+    pub fn new() -> Trace<W> {
+        Trace::default()
+    }
+
+    pub fn records(&self) -> &[TraceRecord<W>] {
+        &self.records
+    }
+
+    pub fn into_records(self) -> Vec<TraceRecord<W>> {
+        self.records
+    }
+
+    /// the index of the first record at which the traces differ, if they do.
+    /// if one trace is a prefix of the other, that's where the shorter one ends.
+    pub fn first_divergence(&self, other: &Trace<W>) -> Option<usize> {
+        let common = self.records.len().min(other.records.len());
+
+        (0..common)
+            .find(|&idx| self.records[idx] != other.records[idx])
+            .or_else(|| Some(common).filter(|_| self.records.len() != other.records.len()))
+    }
+}
+
+//the format stores words as isize varints, so only traces of isize words can be saved
+impl Trace {
+    pub fn save(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+
+        for record in &self.records {
+            let words = record.opcode.encode();
+
+            write_varint(&mut out, record.inst_ptr)?;
+            write_varint(&mut out, words.len() as isize)?;
+            for word in words {
+                write_varint(&mut out, word)?;
+            }
+
+            write_varint(&mut out, record.reads.len() as isize)?;
+            for &val in &record.reads {
+                write_varint(&mut out, val)?;
+            }
+
+            write_varint(&mut out, record.writes.len() as isize)?;
+            for &(addr, val) in &record.writes {
+                write_varint(&mut out, addr)?;
+                write_varint(&mut out, val)?;
+            }
+        }
+
+        out.flush()
+    }
+
+    /// loads a saved trace, decoding its instructions with `instructions`.
+    pub fn load(input: impl Read, instructions: &InstructionSet) -> io::Result<Trace> {
+        let mut bytes = io::BufReader::new(input).bytes();
+
+        let mut header = [0; 5];
+        for byte in header.iter_mut() {
+            *byte = bytes.next().ok_or_else(|| invalid_data("truncated header"))??;
+        }
+
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not an Intcode trace"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("unsupported trace version"));
+        }
+
+        let mut records = Vec::new();
+
+        //every record's instruction is written to the start of its memory and decoded there
+        let mut decoder = IntcodeRunner::new(&[]);
+        decoder.set_instructions(instructions.clone());
+
+        while let Some(inst_ptr) = read_varint(&mut bytes)? {
+            let words = read_list(&mut bytes, 1)?;
+            for addr in 0..4 {
+                decoder.write(addr, words.get(addr).copied().unwrap_or_default());
+            }
+
+            let opcode = decoder
+                .decode_at(0)
+                .map_err(|_| invalid_data("undecodable instruction"))?;
+
+            let reads = read_list(&mut bytes, 1)?;
+            let writes = read_list(&mut bytes, 2)?
+                .chunks(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect();
+
+            records.push(TraceRecord {
+                inst_ptr,
+                opcode,
+                reads,
+                writes,
+            });
+        }
+
+        Ok(Trace {
+            records,
+            pending: None,
+        })
+    }
+}
+
+impl<W: Word> fmt::Display for Trace<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for record in &self.records {
+            writeln!(f, "{}", record)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint(out: &mut impl Write, val: isize) -> io::Result<()> {
+    let mut zigzag = ((val << 1) ^ (val >> (isize::BITS - 1))) as usize;
+
+    loop {
+        let byte = (zigzag & 0x7f) as u8;
+        zigzag >>= 7;
+
+        if zigzag == 0 {
+            return out.write_all(&[byte]);
+        }
+
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// reads a varint, or None if the input ended cleanly before it.
+fn read_varint(bytes: &mut impl Iterator<Item = io::Result<u8>>) -> io::Result<Option<isize>> {
+    let mut zigzag: usize = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = match bytes.next() {
+            Some(byte) => byte?,
+            None if shift == 0 => return Ok(None),
+            None => return Err(invalid_data("truncated varint")),
+        };
+
+        if shift >= usize::BITS {
+            return Err(invalid_data("varint is too long"));
+        }
+
+        zigzag |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(Some((zigzag >> 1) as isize ^ -((zigzag & 1) as isize)));
+        }
+    }
+}
+
+/// reads a count, followed by count * `group_size` varints.
+fn read_list(bytes: &mut impl Iterator<Item = io::Result<u8>>, group_size: usize) -> io::Result<Vec<isize>> {
+    let mut next = || read_varint(bytes)?.ok_or_else(|| invalid_data("truncated record"));
+
+    let len = next()?;
+    if len < 0 {
+        return Err(invalid_data("negative length"));
+    }
+
+    (0..len as usize * group_size).map(|_| next()).collect()
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    /// starts recording every executed instruction, discarding any previous trace.
+    pub fn start_tracing(&mut self) {
+        self.trace = Some(Trace::new());
+    }
+
+    /// stops recording, returning what was recorded.
+    pub fn stop_tracing(&mut self) -> Option<Trace<W>> {
+        self.trace.take()
+    }
+
+    pub fn trace(&self) -> Option<&Trace<W>> {
+        self.trace.as_ref()
+    }
+
+    pub(super) fn trace_begin(&mut self, opcode: &Opcode<W>) {
+        if let Some(trace) = &mut self.trace {
+            trace.pending = Some(TraceRecord {
+                inst_ptr: self.inst_ptr,
+                opcode: opcode.clone(),
+                reads: Vec::new(),
+                writes: Vec::new(),
+            });
+        }
+    }
+
+    /// records the instruction being executed, unless it didn't complete.
+    pub(super) fn trace_end(&mut self, completed: bool) {
+        if let Some(trace) = &mut self.trace {
+            if let Some(record) = trace.pending.take().filter(|_| completed) {
+                trace.records.push(record);
+            }
+        }
+    }
+
+    pub(super) fn trace_read(&mut self, val: W) {
+        if let Some(TraceRecord { reads, .. }) = self.trace.as_mut().and_then(|trace| trace.pending.as_mut()) {
+            reads.push(val);
+        }
+    }
+
+    pub(super) fn trace_write(&mut self, addr: isize, val: W) {
+        if let Some(TraceRecord { writes, .. }) = self.trace.as_mut().and_then(|trace| trace.pending.as_mut()) {
+            writes.push((addr, val));
+        }
+    }
+}
+
+#[test]
+fn records_and_round_trips() {
+    //day2's first example: 1 + 1 = 2
+    let mem = vec![1, 0, 0, 0, 4, 0, 99];
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.start_tracing();
+    runner.outputs().for_each(drop);
+    let trace = runner.stop_tracing().unwrap();
+
+    let expected = "     0: add [0], [0], [0]                ; read 1, 1; [0] <- 2
+     4: out [0]                          ; read 2
+     6: halt
+";
+    assert_eq!(trace.to_string(), expected);
+
+    let mut saved = Vec::new();
+    trace.save(&mut saved).unwrap();
+    let loaded = Trace::load(&saved[..], &InstructionSet::standard()).unwrap();
+    assert_eq!(loaded.records(), trace.records());
+    assert_eq!(loaded.first_divergence(&trace), None);
+}
+
+#[test]
+fn loads_extensions() {
+    use crate::intcode::extensions::reboot;
+
+    //sets a flag at 9 and reboots, then halts once the flag is set
+    let mem = vec![1005, 9, 8, 1101, 1, 0, 9, 0, 99, 0];
+    let mut instructions = InstructionSet::standard();
+    instructions.register(reboot());
+
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.set_instructions(instructions.clone());
+    runner.start_tracing();
+    runner.outputs().for_each(drop);
+    let trace = runner.stop_tracing().unwrap();
+
+    let mut saved = Vec::new();
+    trace.save(&mut saved).unwrap();
+    assert!(Trace::load(&saved[..], &InstructionSet::standard()).is_err());
+
+    let loaded = Trace::load(&saved[..], &instructions).unwrap();
+    assert_eq!(loaded.records(), trace.records());
+    assert!(loaded.records().iter().any(|record| record.opcode == reboot().opcode(vec![])));
+}
+
+#[test]
+fn finds_divergence() {
+    let mem = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+
+    let trace_with_input = |input| {
+        let mut runner = IntcodeRunner::new(&mem);
+        runner.push_input(input);
+        runner.start_tracing();
+        runner.outputs().for_each(drop);
+        runner.stop_tracing().unwrap()
+    };
+
+    //the input is the first thing written, and that's where the traces differ
+    assert_eq!(trace_with_input(8).first_divergence(&trace_with_input(7)), Some(0));
+    assert_eq!(trace_with_input(8).first_divergence(&trace_with_input(8)), None);
+}
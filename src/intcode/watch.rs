@@ -0,0 +1,165 @@
+//! memory watchpoints, triggered by the reads and writes instructions make.
+//! fetching the instructions themselves doesn't trigger them - use a breakpoint for that.
+
+use crate::intcode::{IntcodeRunner, Word};
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WatchAction {
+    /// stop running once the instruction completes, reporting RunState::Watchpoint.
+    Pause,
+    /// keep running, but add the access to the watch log.
+    Log,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub addrs: Range<isize>,
+    pub on_read: bool,
+    pub on_write: bool,
+    pub action: WatchAction,
+}
+
+impl Watchpoint {
+    pub fn reads(addrs: Range<isize>, action: WatchAction) -> Watchpoint {
+        Watchpoint {
+            addrs,
+            on_read: true,
+            on_write: false,
+            action,
+        }
+    }
+
+    pub fn writes(addrs: Range<isize>, action: WatchAction) -> Watchpoint {
+        Watchpoint {
+            addrs,
+            on_read: false,
+            on_write: true,
+            action,
+        }
+    }
+
+    pub fn accesses(addrs: Range<isize>, action: WatchAction) -> Watchpoint {
+        Watchpoint {
+            addrs,
+            on_read: true,
+            on_write: true,
+            action,
+        }
+    }
+
+    fn is_triggered_by(&self, addr: isize, access: Access) -> bool {
+        let watches_access = match access {
+            Access::Read => self.on_read,
+            Access::Write => self.on_write,
+        };
+
+        watches_access && self.addrs.contains(&addr)
+    }
+}
+
+/// a single access to a watched address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WatchHit<W = isize> {
+    /// the instruction that made the access.
+    pub inst_ptr: isize,
+    pub addr: isize,
+    pub access: Access,
+    /// the value read, or the new value written.
+    pub value: W,
+}
+
+impl<W: Word> fmt::Display for WatchHit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (access, preposition) = match self.access {
+            Access::Read => ("read", "from"),
+            Access::Write => ("write", "to"),
+        };
+
+        write!(
+            f,
+            "{} of {} {} [{}] by the instruction at {}",
+            access, self.value, preposition, self.addr, self.inst_ptr
+        )
+    }
+}
+
+impl<W: Word> IntcodeRunner<W> {
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// removes every watchpoint that covers `addr`. returns false if there were none.
+    pub fn remove_watchpoints_at(&mut self, addr: isize) -> bool {
+        let num_watchpoints = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| !watchpoint.addrs.contains(&addr));
+
+        self.watchpoints.len() != num_watchpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// every access made to addresses watched with WatchAction::Log, oldest first.
+    pub fn watch_log(&self) -> &[WatchHit<W>] {
+        &self.watch_log
+    }
+
+    pub fn take_watch_log(&mut self) -> Vec<WatchHit<W>> {
+        std::mem::take(&mut self.watch_log)
+    }
+
+    pub(super) fn check_watchpoints(&mut self, addr: isize, access: Access, value: W) {
+        let hit = WatchHit {
+            inst_ptr: self.inst_ptr,
+            addr,
+            access,
+            value,
+        };
+
+        for watchpoint in &self.watchpoints {
+            if !watchpoint.is_triggered_by(addr, access) {
+                continue;
+            }
+
+            match watchpoint.action {
+                WatchAction::Log => self.watch_log.push(hit),
+                //only the first hit is reported, the instruction is what matters
+                WatchAction::Pause => {
+                    self.watch_pause.get_or_insert(hit);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn logs_and_pauses() {
+    use crate::intcode::RunState;
+
+    //reads [9] twice, then writes it
+    let mem = vec![1, 9, 9, 9, 4, 9, 99, 0, 0, 21];
+    let mut runner = IntcodeRunner::new(&mem);
+    runner.add_watchpoint(Watchpoint::reads(9..10, WatchAction::Log));
+    runner.add_watchpoint(Watchpoint::writes(9..10, WatchAction::Pause));
+
+    let hit = WatchHit {
+        inst_ptr: 0,
+        addr: 9,
+        access: Access::Write,
+        value: 42,
+    };
+    assert_eq!(runner.run(), Ok(RunState::Watchpoint(hit)));
+    assert_eq!(runner.watch_log().len(), 2);
+
+    assert_eq!(runner.run(), Ok(RunState::Output(42)));
+    assert_eq!(runner.watch_log().last().map(|hit| hit.inst_ptr), Some(4));
+}
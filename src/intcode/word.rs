@@ -0,0 +1,114 @@
+//! the types an Intcode machine can use as its memory words.
+//!
+//! addresses, the instruction pointer and the relative base are always isize, whatever the word
+//! type is. words that have to be used as addresses but don't fit are reported as faults.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+pub trait Word: Copy + Default + Eq + Ord + Hash + fmt::Debug + fmt::Display + FromStr + Send + Sync + 'static {
+    fn from_isize(val: isize) -> Self;
+
+    /// None if the word doesn't fit in an isize.
+    fn to_isize(self) -> Option<isize>;
+
+    /// the sum, or None if it overflowed and the type reports overflow.
+    fn sum(self, other: Self) -> Option<Self>;
+
+    /// the product, or None if it overflowed and the type reports overflow.
+    fn product(self, other: Self) -> Option<Self>;
+}
+
+// the primitive types wrap around on overflow, like the VM always did in release builds.
+macro_rules! impl_wrapping_word {
+    ($($ty:ty),*) => {
+        $(
+            impl Word for $ty {
+                fn from_isize(val: isize) -> Self {
+                    val as $ty
+                }
+
+                fn to_isize(self) -> Option<isize> {
+                    isize::try_from(self).ok()
+                }
+
+                fn sum(self, other: Self) -> Option<Self> {
+                    Some(self.wrapping_add(other))
+                }
+
+                fn product(self, other: Self) -> Option<Self> {
+                    Some(self.wrapping_mul(other))
+                }
+            }
+        )*
+    };
+}
+
+impl_wrapping_word!(isize, i64, i128);
+
+/// an i64 word that faults with IntcodeError::Overflow instead of wrapping around.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CheckedI64(pub i64);
+
+impl Word for CheckedI64 {
+    fn from_isize(val: isize) -> Self {
+        CheckedI64(val as i64)
+    }
+
+    fn to_isize(self) -> Option<isize> {
+        isize::try_from(self.0).ok()
+    }
+
+    fn sum(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(CheckedI64)
+    }
+
+    fn product(self, other: Self) -> Option<Self> {
+        self.0.checked_mul(other.0).map(CheckedI64)
+    }
+}
+
+impl fmt::Display for CheckedI64 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for CheckedI64 {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(CheckedI64)
+    }
+}
+
+#[test]
+fn i128_holds_products_that_overflow_i64() {
+    use crate::intcode::IntcodeRunner;
+
+    //multiplies its immediate operands into [7], then outputs it
+    let mem: Vec<i128> = vec![1102, 1_000_000_000_000_000_000, 1000, 7, 4, 7, 99, 0];
+    let mut runner = IntcodeRunner::from_words(&mem);
+
+    let outputs: Vec<i128> = runner.outputs().collect::<Result<_, _>>().unwrap();
+    assert_eq!(outputs, vec![1_000_000_000_000_000_000_000]);
+}
+
+#[test]
+fn checked_words_report_overflow() {
+    use crate::intcode::{IntcodeError, IntcodeRunner};
+
+    let mem: Vec<CheckedI64> = [1102, i64::MAX, 2, 7, 4, 7, 99, 0]
+        .iter()
+        .map(|&word| CheckedI64(word))
+        .collect();
+    let mut runner = IntcodeRunner::from_words(&mem);
+
+    match runner.run() {
+        Err(IntcodeError::Overflow(fault)) => assert_eq!(fault.inst_ptr, 0),
+        other => panic!("expected an overflow, got {:?}", other),
+    }
+}
@@ -6,7 +6,7 @@ extern crate cascade;
 
 use aoc_runner_derive::aoc_lib;
 
-mod intcode;
+pub mod intcode;
 
 //mod day1;
 //mod day2;